@@ -0,0 +1,473 @@
+//! Everything the user can do from the menus, the toolbar, note context menus,
+//! keyboard shortcuts and the command palette goes through Action.
+use eframe::egui::{self};
+use log::{info};
+use crate::app::{App, SidebarTab, TrashAction};
+use crate::db::models::{NoteIdName};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    // archive
+    CreateArchive,
+    OpenArchive,
+    Import,
+    ExportMd,
+    ExportHtml,
+    BackupNow,
+    RestoreBackup,
+    Maintenance,
+    Settings,
+    // note
+    NewNote,
+    NewChildNote,
+    SaveNote,
+    RenameNote,
+    EncryptNote,
+    LockNote,
+    RemoveEncryption,
+    DeleteNote,
+    History,
+    NextNote,
+    PreviousNote,
+    Today,
+    Templates,
+    // view
+    TogglePreview,
+    ToggleOutline,
+    ToggleCalendar,
+    ShowNotes,
+    ShowTrash,
+    ReloadNotes,
+    Search,
+    Tasks,
+    QuickOpen,
+    CommandPalette,
+    // help
+    About,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Menu {
+    Archive,
+    Note,
+    View,
+    Help,
+}
+
+impl Menu {
+    pub const ALL: [Menu; 4] = [Menu::Archive, Menu::Note, Menu::View, Menu::Help];
+
+    pub fn title(self) -> &'static str {
+        match self {
+            Menu::Archive => "Archive",
+            Menu::Note => "Note",
+            Menu::View => "View",
+            Menu::Help => "Help",
+        }
+    }
+}
+
+impl Action {
+    /// In menu order
+    pub const ALL: [Action; 33] = [
+        Action::CreateArchive,
+        Action::OpenArchive,
+        Action::Import,
+        Action::ExportMd,
+        Action::ExportHtml,
+        Action::BackupNow,
+        Action::RestoreBackup,
+        Action::Maintenance,
+        Action::Settings,
+        Action::NewNote,
+        Action::NewChildNote,
+        Action::SaveNote,
+        Action::RenameNote,
+        Action::EncryptNote,
+        Action::LockNote,
+        Action::RemoveEncryption,
+        Action::DeleteNote,
+        Action::History,
+        Action::NextNote,
+        Action::PreviousNote,
+        Action::Today,
+        Action::Templates,
+        Action::TogglePreview,
+        Action::ToggleOutline,
+        Action::ToggleCalendar,
+        Action::ShowNotes,
+        Action::ShowTrash,
+        Action::ReloadNotes,
+        Action::Search,
+        Action::Tasks,
+        Action::QuickOpen,
+        Action::CommandPalette,
+        Action::About,
+    ];
+
+    /// Entries of the note context menu in the sidebar
+    pub const NOTE_CONTEXT: [Action; 6] = [
+        Action::NewChildNote,
+        Action::RenameNote,
+        Action::EncryptNote,
+        Action::LockNote,
+        Action::RemoveEncryption,
+        Action::DeleteNote,
+    ];
+
+    pub fn title(self) -> &'static str {
+        match self {
+            Action::CreateArchive => "Create",
+            Action::OpenArchive => "Open",
+            Action::Import => "Import *.md",
+            Action::ExportMd => "Export to *.md",
+            Action::ExportHtml => "Export to *.html",
+            Action::BackupNow => "Backup now",
+            Action::RestoreBackup => "Restore from backup",
+            Action::Maintenance => "Maintenance",
+            Action::Settings => "Settings",
+            Action::NewNote => "New note",
+            Action::NewChildNote => "Add child note",
+            Action::SaveNote => "Save",
+            Action::RenameNote => "Rename",
+            Action::EncryptNote => "Encrypt",
+            Action::LockNote => "Lock",
+            Action::RemoveEncryption => "Remove encryption",
+            Action::DeleteNote => "Delete",
+            Action::History => "History",
+            Action::NextNote => "Next note",
+            Action::PreviousNote => "Previous note",
+            Action::Today => "Today's journal entry",
+            Action::Templates => "Templates",
+            Action::TogglePreview => "Toggle preview",
+            Action::ToggleOutline => "Toggle outline",
+            Action::ToggleCalendar => "Toggle calendar",
+            Action::ShowNotes => "Notes",
+            Action::ShowTrash => "Trash",
+            Action::ReloadNotes => "Reload notes",
+            Action::Search => "Search",
+            Action::Tasks => "Tasks",
+            Action::QuickOpen => "Quick open",
+            Action::CommandPalette => "Command palette",
+            Action::About => "About",
+        }
+    }
+
+    /// Menu of the action and the group inside of it, groups are separated
+    pub fn menu(self) -> (Menu, u8) {
+        match self {
+            Action::CreateArchive | Action::OpenArchive => (Menu::Archive, 0),
+            Action::Import => (Menu::Archive, 1),
+            Action::ExportMd | Action::ExportHtml => (Menu::Archive, 2),
+            Action::BackupNow | Action::RestoreBackup | Action::Maintenance => (Menu::Archive, 3),
+            Action::Settings => (Menu::Archive, 4),
+            Action::NewNote | Action::NewChildNote | Action::SaveNote
+                | Action::RenameNote => (Menu::Note, 0),
+            Action::EncryptNote | Action::LockNote | Action::RemoveEncryption => (Menu::Note, 1),
+            Action::DeleteNote => (Menu::Note, 2),
+            Action::History => (Menu::Note, 3),
+            Action::NextNote | Action::PreviousNote => (Menu::Note, 4),
+            Action::Today | Action::Templates => (Menu::Note, 5),
+            Action::TogglePreview | Action::ToggleOutline | Action::ToggleCalendar
+                | Action::ShowNotes | Action::ShowTrash => (Menu::View, 0),
+            Action::ReloadNotes => (Menu::View, 1),
+            Action::Search | Action::Tasks | Action::QuickOpen | Action::CommandPalette => (Menu::View, 2),
+            Action::About => (Menu::Help, 0),
+        }
+    }
+
+    /// Name in the [keybindings] table of config.toml
+    pub fn id(self) -> &'static str {
+        match self {
+            Action::CreateArchive => "create_archive",
+            Action::OpenArchive => "open_archive",
+            Action::Import => "import",
+            Action::ExportMd => "export_md",
+            Action::ExportHtml => "export_html",
+            Action::BackupNow => "backup_now",
+            Action::RestoreBackup => "restore_backup",
+            Action::Maintenance => "maintenance",
+            Action::Settings => "settings",
+            Action::NewNote => "new_note",
+            Action::NewChildNote => "new_child_note",
+            Action::SaveNote => "save_note",
+            Action::RenameNote => "rename_note",
+            Action::EncryptNote => "encrypt_note",
+            Action::LockNote => "lock_note",
+            Action::RemoveEncryption => "remove_encryption",
+            Action::DeleteNote => "delete_note",
+            Action::History => "history",
+            Action::NextNote => "next_note",
+            Action::PreviousNote => "previous_note",
+            Action::Today => "today",
+            Action::Templates => "templates",
+            Action::TogglePreview => "toggle_preview",
+            Action::ToggleOutline => "toggle_outline",
+            Action::ToggleCalendar => "toggle_calendar",
+            Action::ShowNotes => "show_notes",
+            Action::ShowTrash => "show_trash",
+            Action::ReloadNotes => "reload_notes",
+            Action::Search => "search",
+            Action::Tasks => "tasks",
+            Action::QuickOpen => "quick_open",
+            Action::CommandPalette => "command_palette",
+            Action::About => "about",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Action> {
+        Action::ALL.into_iter().find(|x| x.id() == id)
+    }
+
+    /// Shortcut when config.toml doesn't set one, see keymap.rs
+    pub fn default_binding(self) -> Option<&'static str> {
+        let x = match self {
+            Action::NewNote => "Ctrl+N",
+            Action::SaveNote => "Ctrl+S",
+            Action::RenameNote => "F2",
+            Action::DeleteNote => "Ctrl+Shift+Delete",
+            Action::History => "Ctrl+H",
+            Action::NextNote => "Alt+Down",
+            Action::PreviousNote => "Alt+Up",
+            Action::TogglePreview => "Ctrl+E",
+            Action::ToggleOutline => "Ctrl+Shift+O",
+            Action::Today => "Ctrl+J",
+            Action::Search => "Ctrl+F",
+            Action::Tasks => "Ctrl+T",
+            Action::QuickOpen => "Ctrl+P",
+            Action::CommandPalette => "Ctrl+Shift+P",
+            _ => return None,
+        };
+        Some(x)
+    }
+}
+
+impl App {
+    pub fn is_action_enabled(&self, action: Action) -> bool {
+        self.is_action_enabled_for(action, self.selected_index)
+    }
+
+    /// Same as is_action_enabled, note actions target the note with id
+    pub fn is_action_enabled_for(&self, action: Action, id: Option<i64>) -> bool {
+        let note = id.and_then(|x| find_note(&self.names, x));
+        match action {
+            Action::NewChildNote => note.is_some_and(|x| !x.has_parent || !x.children.is_empty()),
+            Action::RenameNote | Action::DeleteNote => note.is_some(),
+            Action::EncryptNote => note.is_some_and(|x| !x.is_encrypted),
+            Action::LockNote | Action::RemoveEncryption => note
+                .is_some_and(|x| x.is_encrypted && self.note_keys.contains_key(&x.id)),
+            Action::SaveNote => self.selected_index.is_some() && self.should_save(),
+            Action::History => self.edited_note_id.is_some(),
+            Action::NextNote | Action::PreviousNote => !self.names.is_empty(),
+            _ => true,
+        }
+    }
+
+    pub fn run_action(&mut self, action: Action) {
+        self.run_action_for(action, self.selected_index);
+    }
+
+    /// Same as run_action, note actions target the note with id
+    pub fn run_action_for(&mut self, action: Action, id: Option<i64>) {
+        if !self.is_action_enabled_for(action, id) {
+            return;
+        }
+        info!("Action: {:?}", action);
+        match action {
+            Action::CreateArchive => {
+                let _ = self.create_db();
+            }
+            Action::OpenArchive => {
+                self.open_archive();
+                self.load_rows = false;
+            }
+            Action::Import => {
+                let _ = self.import();
+            }
+            Action::ExportMd => {
+                let _ = self.export("md");
+            }
+            Action::ExportHtml => {
+                let _ = self.export("html");
+            }
+            Action::BackupNow => self.backup_now(),
+            Action::RestoreBackup => {
+                self.state_backups_open = true;
+                self.state_backups_load = false;
+            }
+            Action::Maintenance => self.state_maintenance_open = true,
+            Action::Settings => self.show_settings = true,
+            Action::NewNote => {
+                self.parent_note_id = None;
+                self.state_add_new_note = true;
+            }
+            Action::NewChildNote => {
+                // selected note is parent
+                self.parent_note_id = id;
+                self.state_add_new_note = true;
+            }
+            Action::SaveNote => {
+                let _ = self.try_update_note_content();
+            }
+            Action::RenameNote => {
+                if let Some(note) = id.and_then(|x| find_note(&self.names, x)) {
+                    self.rename_input = note.name.clone();
+                    self.selected_index = Some(note.id);
+                    // show popup with name as input
+                    self.state_rename = true;
+                }
+            }
+            Action::EncryptNote => {
+                self.encrypt_note_id = id;
+                self.state_encrypt_note = true;
+            }
+            Action::LockNote => {
+                if let Some(x) = id {
+                    let _ = self.try_lock_note(x);
+                }
+            }
+            Action::RemoveEncryption => {
+                if let Some(x) = id {
+                    let _ = self.try_remove_encryption(x);
+                }
+            }
+            Action::DeleteNote => {
+                if let Some(note) = id.and_then(|x| find_note(&self.names, x)) {
+                    self.trash_confirm = Some(TrashAction::MoveToTrash(note.id, note.name.clone()));
+                }
+            }
+            Action::History => self.state_history_open = true,
+            Action::NextNote => self.select_next_note(1),
+            Action::PreviousNote => self.select_next_note(-1),
+            Action::Today => {
+                let res = self.try_open_journal_day(chrono::Local::now().date_naive());
+                self.status_error = crate::utils::result(res, "Error opening journal entry");
+            }
+            Action::Templates => self.state_templates_open = true,
+            Action::TogglePreview => {
+                self.state_is_right_panel_on = !self.state_is_right_panel_on;
+            }
+            Action::ToggleOutline => self.state_outline_open = !self.state_outline_open,
+            Action::ToggleCalendar => self.state_calendar_open = !self.state_calendar_open,
+            Action::ShowNotes => self.select_tab(SidebarTab::Notes),
+            Action::ShowTrash => self.select_tab(SidebarTab::Trash),
+            Action::ReloadNotes => self.load_rows = false,
+            Action::Search => {
+                self.state_search = true;
+                self.search_has_focus = true;
+            }
+            Action::Tasks => {
+                self.state_tasks_open = true;
+                self.state_tasks_load = false;
+            }
+            Action::QuickOpen => self.open_quick_open(),
+            Action::CommandPalette => self.open_command_palette(),
+            Action::About => self.show_about = true,
+        }
+    }
+
+    fn select_tab(&mut self, tab: SidebarTab) {
+        self.selected_tab = tab;
+        self.selected_index = None;
+        self.state_rename = false;
+    }
+
+    /// Moves the selection through the note tree, in the sidebar order
+    fn select_next_note(&mut self, step: isize) {
+        let mut ids = Vec::new();
+        flatten_ids(&self.names, &mut ids);
+        if ids.is_empty() {
+            return;
+        }
+        let next = match self.selected_index.and_then(|x| ids.iter().position(|id| *id == x)) {
+            Some(i) => (i as isize + step).rem_euclid(ids.len() as isize) as usize,
+            None if step > 0 => 0,
+            None => ids.len() - 1,
+        };
+        self.open_search_result(ids[next], false);
+    }
+
+    /// Runs actions whose shortcuts were pressed in this frame. While a dialog
+    /// is open or a text field has the focus only saving and the command
+    /// palette have shortcuts, other keys are left to the dialog or the field.
+    pub fn handle_shortcuts(&mut self, ctx: &egui::Context) {
+        let busy = self.is_dialog_open() || ctx.wants_keyboard_input();
+        let bindings = self.keymap.bindings().to_vec();
+        for (action, shortcut) in bindings {
+            if busy && !matches!(action, Action::SaveNote | Action::CommandPalette) {
+                continue;
+            }
+            if ctx.input_mut(|i| i.consume_shortcut(&shortcut)) {
+                self.run_action(action);
+            }
+        }
+    }
+
+    /// Windows that wait for an answer. Tool windows like Tasks or History
+    /// aren't dialogs, their text fields are covered by wants_keyboard_input.
+    fn is_dialog_open(&self) -> bool {
+        self.state_rename
+            || self.show_settings
+            || self.state_add_new_note
+            || self.state_encrypt_note
+            || self.trash_confirm.is_some()
+            || self.io_operation.is_some()
+            || self.io_result
+            || self.show_about
+            || self.state_search
+            || self.state_quick_open
+            || self.state_command_palette
+    }
+
+    /// Menu entries of the actions, disabled ones are greyed out
+    pub fn action_menu(&mut self, ui: &mut egui::Ui, menu: Menu) {
+        let mut group = None;
+        for action in Action::ALL.iter().filter(|x| x.menu().0 == menu) {
+            let (_, g) = action.menu();
+            if group.is_some_and(|x| x != g) {
+                ui.separator();
+            }
+            group = Some(g);
+
+            let mut button = egui::Button::new(action.title());
+            if let Some(s) = self.keymap.shortcut(*action) {
+                button = button.shortcut_text(ui.ctx().format_shortcut(&s));
+            }
+            if ui.add_enabled(self.is_action_enabled(*action), button).clicked() {
+                self.run_action(*action);
+                ui.close_menu();
+            }
+        }
+    }
+
+    /// Context menu of a note in the sidebar, only actions that apply to it
+    pub fn note_context_menu(&mut self, ui: &mut egui::Ui, note: &NoteIdName) {
+        for action in Action::NOTE_CONTEXT {
+            if self.is_action_enabled_for(action, Some(note.id))
+                && ui.button(action.title()).clicked() {
+                self.run_action_for(action, Some(note.id));
+                ui.close_menu();
+            }
+        }
+    }
+}
+
+fn flatten_ids(notes: &[NoteIdName], ids: &mut Vec<i64>) {
+    for note in notes {
+        ids.push(note.id);
+        flatten_ids(&note.children, ids);
+    }
+}
+
+/// Note with id anywhere in the tree
+pub fn find_note(notes: &[NoteIdName], id: i64) -> Option<&NoteIdName> {
+    for note in notes {
+        if note.id == id {
+            return Some(note);
+        }
+        if let Some(x) = find_note(&note.children, id) {
+            return Some(x);
+        }
+    }
+    None
+}
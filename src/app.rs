@@ -5,10 +5,20 @@ use crate::config::Config;
 use std::error::Error;
 use eframe::egui;
 use std::sync::{Arc, atomic::{AtomicBool, Ordering}};
+use std::collections::HashMap;
 // replace NoteIdName to Note
-use crate::db::models::{NoteIdName, Note};
+use crate::db::models::{NoteIdName, Note, ArchiveStats, TrashItem, SearchResult, SavedSearch, NoteRef, Task, Template, NoteDays};
+use crate::ui::modal_quick_open::QuickOpenMatch;
 use crate::font::FontManager;
 use crate::constants::{DEFAULT_FONT_DIR, DEFAULT_FONT};
+use crate::crypto::NoteKey;
+use crate::backup::BackupInfo;
+use crate::actions::Action;
+use crate::keymap::Keymap;
+use crate::embeds::EmbedCache;
+use crate::journal::JournalSettings;
+use chrono::NaiveDate;
+use crate::markdown::{Extensions, SourceMap};
 
 #[derive(PartialEq)]
 pub enum SidebarTab {
@@ -16,6 +26,34 @@ pub enum SidebarTab {
     Trash,
 }
 
+/// Trash actions that wait for confirmation
+pub enum TrashAction {
+    MoveToTrash(i64, String), // a note with its children, it can be restored
+    Delete(i64, String),
+    Empty,
+    RestoreToRoot(i64, String),
+}
+
+/// How the Tasks window groups task items
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum TaskGrouping {
+    #[default]
+    Note,
+    Tag,
+}
+
+/// Which task items the Tasks window shows
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum TaskFilter {
+    #[default]
+    Open,
+    Done,
+    All,
+}
+
+/// (results are in the trash, results) or an error of a saved search
+pub type SmartFolderResult = Result<(bool, Vec<SearchResult>), String>;
+
 pub enum IoOperation {
     Import,
     Export,
@@ -40,8 +78,11 @@ pub struct App {
     pub rename_error: Option<String>,
     pub db_error: Option<String>,
     pub load_rows: bool, // trigger loading
-    pub notes_deleted: Vec<(i64, String)>,
+    pub notes_deleted: Vec<TrashItem>,
     pub state_trash_load: bool, // trigger loading
+    pub trash_confirm: Option<TrashAction>,
+    pub trash_preview: Option<Note>,
+    pub trash_preview_parent: Option<String>,
     pub selected_index: Option<i64>,
     pub state_start: bool,
     pub selected_tab: SidebarTab,
@@ -53,10 +94,26 @@ pub struct App {
     pub parent_note_id: Option<i64>,
     pub add_new_note_input: String,
     pub add_new_note_error: Option<String>,
+    pub add_new_note_template: Option<i64>,
+    pub add_new_note_fields: HashMap<String, String>, // values of "{{?Field}}"
     pub original_content: String,
     pub edited_content: String,
     pub edited_note_id: Option<i64>,
     pub state_is_right_panel_on: bool,
+    pub state_outline_open: bool,
+    pub editor_jump: Option<usize>, // offset to move the editor cursor to
+    pub editor_cursor: Option<usize>, // offset, to notice cursor moves
+    pub editor_scroll: f32,
+    pub preview_scroll_to: Option<f32>,
+    pub preview_map: SourceMap, // of the last drawn preview
+    pub markdown_extensions: Extensions, // loaded from the archive
+    pub embed_cache: EmbedCache, // notes embedded in the preview
+    pub journal: JournalSettings, // loaded from the archive
+    pub state_calendar_open: bool,
+    pub state_calendar_load: bool, // trigger loading
+    pub calendar_month: NaiveDate, // first day of the shown month
+    pub calendar_day: Option<NaiveDate>, // its notes are listed
+    pub calendar_notes: Vec<NoteDays>, // of the shown month
     pub state_is_dark_mode: bool,
 
     pub import_done: Arc<AtomicBool>,
@@ -75,12 +132,66 @@ pub struct App {
     pub status_error: String, // global error
     pub search_input: String,
     pub state_search: bool,
-    pub search_result: Vec<Note>,
+    pub search_result: Vec<SearchResult>,
+    pub search_in_trash: bool, // results are trashed notes
+    pub search_error: Option<String>,
+    pub save_search_name: String,
+    pub saved_searches: Vec<SavedSearch>,
+    pub state_saved_searches_load: bool, // trigger loading
+    pub saved_search_results: HashMap<i64, SmartFolderResult>,
     pub search_has_focus: bool,
+    pub state_quick_open: bool,
+    pub state_quick_open_load: bool, // trigger loading
+    pub quick_open_input: String,
+    pub quick_open_has_focus: bool,
+    pub quick_open_notes: Vec<NoteRef>,
+    pub quick_open_matches: Vec<QuickOpenMatch>,
+    pub quick_open_selected: usize,
+    pub keymap: Keymap,
+    pub keybinding_inputs: HashMap<Action, String>, // edited in settings
+    pub keybinding_errors: HashMap<Action, String>,
+    pub state_command_palette: bool,
+    pub command_palette_input: String,
+    pub command_palette_has_focus: bool,
+    pub command_palette_selected: usize,
+    pub state_tasks_open: bool,
+    pub state_tasks_load: bool, // trigger loading
+    pub tasks: Vec<Task>,
+    pub task_grouping: TaskGrouping,
+    pub task_filter: TaskFilter,
+    pub templates: Vec<Template>,
+    pub state_templates_load: bool, // trigger loading
+    pub state_templates_open: bool,
+    pub template_edit_id: Option<i64>, // None for a new template
+    pub template_name_input: String,
+    pub template_content_input: String,
+    pub template_error: Option<String>,
     pub current_font: String,
     pub font_manager: FontManager,
 
     pub state_history_open: bool,
+
+    // per-note encryption, keys live only for the session
+    pub note_keys: HashMap<i64, NoteKey>,
+    pub locked_note_id: Option<i64>,
+    pub unlock_input: String,
+    pub unlock_error: Option<String>,
+    pub state_encrypt_note: bool,
+    pub encrypt_note_id: Option<i64>,
+    pub encrypt_input: String,
+    pub encrypt_confirm_input: String,
+    pub encrypt_error: Option<String>,
+
+    pub state_backups_open: bool,
+    pub state_backups_load: bool, // trigger loading
+    pub backups: Vec<BackupInfo>,
+    pub backup_count_rx: Option<std::sync::mpsc::Receiver<(PathBuf, Option<i64>)>>, // note counts of backups
+    pub restore_backup_target: Option<PathBuf>,
+    pub last_backup_at: Option<std::time::Instant>,
+
+    pub state_maintenance_open: bool,
+    pub maintenance_log: Vec<String>,
+    pub archive_stats: Option<ArchiveStats>,
 }
 
 impl Default for SidebarTab {
@@ -97,6 +208,7 @@ impl App {
             Some(x) => x,
             None => DEFAULT_FONT_DIR.to_string(),
         };
+        let keymap = Keymap::new(&config.keybindings);
 
         Self {
             db_path: String::new(),
@@ -107,8 +219,11 @@ impl App {
             rename_error: None,
             db_error: None,
             load_rows: false,
-            notes_deleted: Vec::<(i64, String)>::new(),
+            notes_deleted: Vec::<TrashItem>::new(),
             state_trash_load: false,
+            trash_confirm: None,
+            trash_preview: None,
+            trash_preview_parent: None,
             selected_index: None,
             state_start: false,
             selected_tab: SidebarTab::Notes,
@@ -120,9 +235,25 @@ impl App {
             parent_note_id: None,
             add_new_note_input: String::new(),
             add_new_note_error: None,
+            add_new_note_template: None,
+            add_new_note_fields: HashMap::new(),
             original_content: String::new(),
             edited_content: String::new(),
             edited_note_id: None,
+            state_outline_open: false,
+            editor_jump: None,
+            editor_cursor: None,
+            editor_scroll: 0.0,
+            preview_scroll_to: None,
+            preview_map: SourceMap::default(),
+            markdown_extensions: Extensions::default(),
+            embed_cache: EmbedCache::default(),
+            journal: JournalSettings::default(),
+            state_calendar_open: false,
+            state_calendar_load: false,
+            calendar_month: crate::ui::calendar::first_of_month(chrono::Local::now().date_naive()),
+            calendar_day: None,
+            calendar_notes: Vec::new(),
             state_is_right_panel_on: true,
             state_is_dark_mode: true,
 
@@ -142,12 +273,65 @@ impl App {
             status_error: String::new(),
             search_input: String::new(),
             state_search: false,
-            search_result: Vec::<Note>::new(),
+            search_result: Vec::<SearchResult>::new(),
+            search_in_trash: false,
+            search_error: None,
+            save_search_name: String::new(),
+            saved_searches: Vec::new(),
+            state_saved_searches_load: false,
+            saved_search_results: HashMap::new(),
             search_has_focus: false,
+            state_quick_open: false,
+            state_quick_open_load: false,
+            quick_open_input: String::new(),
+            quick_open_has_focus: false,
+            quick_open_notes: Vec::new(),
+            quick_open_matches: Vec::new(),
+            quick_open_selected: 0,
+            keymap,
+            keybinding_inputs: HashMap::new(),
+            keybinding_errors: HashMap::new(),
+            state_command_palette: false,
+            command_palette_input: String::new(),
+            command_palette_has_focus: false,
+            command_palette_selected: 0,
+            state_tasks_open: false,
+            state_tasks_load: false,
+            tasks: Vec::new(),
+            task_grouping: TaskGrouping::Note,
+            task_filter: TaskFilter::Open,
+            templates: Vec::new(),
+            state_templates_load: false,
+            state_templates_open: false,
+            template_edit_id: None,
+            template_name_input: String::new(),
+            template_content_input: String::new(),
+            template_error: None,
             current_font: String::new(),
             font_manager: FontManager::new(font_dir),
 
             state_history_open: false,
+
+            note_keys: HashMap::new(),
+            locked_note_id: None,
+            unlock_input: String::new(),
+            unlock_error: None,
+            state_encrypt_note: false,
+            encrypt_note_id: None,
+            encrypt_input: String::new(),
+            encrypt_confirm_input: String::new(),
+            encrypt_error: None,
+
+            state_backups_open: false,
+            state_backups_load: false,
+            backups: Vec::<BackupInfo>::new(),
+            backup_count_rx: None,
+            restore_backup_target: None,
+            last_backup_at: None,
+
+            state_maintenance_open: false,
+            maintenance_log: Vec::<String>::new(),
+            archive_stats: None,
         }
     }
 
@@ -159,6 +343,7 @@ impl App {
             if x.exists() {
                 app.db_path = x.to_string_lossy().into_owned();
                 app.state_start = true;
+                app.on_archive_open();
             }
         }
         
@@ -211,6 +396,9 @@ impl App {
                         font_size: self.font_size,
                         is_dark_mode: Some(self.state_is_dark_mode),
                         autosave: Some(true),
+                        trash_retention_days: self.config.trash_retention_days,
+                        backup: self.config.backup.clone(),
+                        keybindings: self.config.keybindings.clone(),
                     };
                     config.save_config();
 
@@ -223,6 +411,7 @@ impl App {
 
                     self.state_start = true;
                     self.db_path = path.to_string_lossy().into_owned();
+                    self.on_archive_open();
                     
                     self.load_rows = false;
                     // get rid of ghost data
@@ -250,6 +439,9 @@ impl App {
                 font_size: self.font_size,
                 is_dark_mode: Some(self.state_is_dark_mode),
                 autosave: Some(true),
+                trash_retention_days: self.config.trash_retention_days,
+                backup: self.config.backup.clone(),
+                keybindings: self.config.keybindings.clone(),
             };
             println!("last archive path: {:?}", config.last_archive_path);
             config.save_config();
@@ -264,6 +456,7 @@ impl App {
             let x = path.clone();
             self.db_path = x.to_string_lossy().into_owned();
             self.state_start = true;
+            self.on_archive_open();
             
             // get rid of ghost data
             self.selected_index = None;
@@ -274,6 +467,86 @@ impl App {
         }
     }
     
+    /// Runs every time an archive is opened or created
+    pub fn on_archive_open(&mut self) {
+        self.reload_archive();
+
+        self.state_backups_load = false;
+        self.last_backup_at = None;
+        if self.config.backup.enabled && self.config.backup.on_open {
+            self.backup_now();
+        }
+
+        self.purge_trash();
+    }
+
+    /// Resets everything loaded from the previous archive (or its previous state)
+    pub fn reload_archive(&mut self) {
+        // keys belong to the previous archive
+        self.note_keys.clear();
+        self.locked_note_id = None;
+        self.state_saved_searches_load = false;
+        self.state_tasks_load = false;
+        self.state_templates_load = false;
+        self.add_new_note_template = None;
+        self.template_edit_id = None;
+        self.embed_cache.clear();
+        self.state_calendar_load = false;
+        self.calendar_day = None;
+
+        match crate::db::database::Database::new(&self.db_path) {
+            Ok(mut db) => {
+                if let Err(e) = db.migrate() {
+                    error!("Failed to migrate archive: {e}");
+                }
+                self.markdown_extensions = match db.get_setting(crate::markdown::EXTENSIONS_SETTING) {
+                    Ok(x) => x.map(|x| Extensions::parse(&x)).unwrap_or_default(),
+                    Err(e) => {
+                        error!("Failed to load markdown extensions: {e}");
+                        Extensions::default()
+                    }
+                };
+                self.journal = match JournalSettings::load(&db) {
+                    Ok(x) => x,
+                    Err(e) => {
+                        error!("Failed to load journal settings: {e}");
+                        JournalSettings::default()
+                    }
+                };
+            }
+            Err(e) => error!("Failed to open archive: {e}"),
+        }
+    }
+
+    /// Runs every frame, makes a backup once the interval has passed
+    pub fn check_scheduled_backup(&mut self) {
+        let backup = &self.config.backup;
+        if !backup.enabled || backup.interval_hours == 0 {
+            return;
+        }
+        let interval = std::time::Duration::from_secs(backup.interval_hours as u64 * 3600);
+        let last = *self.last_backup_at.get_or_insert_with(std::time::Instant::now);
+        if last.elapsed() >= interval {
+            self.backup_now();
+        }
+    }
+
+    pub fn backup_now(&mut self) {
+        if self.db_path.is_empty() {
+            return;
+        }
+        self.last_backup_at = Some(std::time::Instant::now());
+        if let Some(dir) = self.config.backup_dir() {
+            crate::backup::create_backup_in_background(
+                &self.db_path,
+                dir,
+                self.config.backup.clone());
+            self.state_backups_load = false;
+        } else {
+            self.status_error = "No backup directory".to_string();
+        }
+    }
+
     pub fn show_progress_window(
         &mut self,
         ctx: &egui::Context,
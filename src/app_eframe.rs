@@ -1,4 +1,4 @@
-use eframe::egui::{self, Button, Color32, Key, RichText};
+use eframe::egui::{self, Button, Color32, RichText};
 use log::{info};
 use std::sync::{Arc, atomic::{AtomicBool, Ordering}};
 use crate::ui::about::show_about;
@@ -26,6 +26,10 @@ impl eframe::App for App {
         if self.state_add_new_note {
             self.show_add_new_note(ctx);
         }
+
+        if self.state_encrypt_note {
+            self.show_encrypt_note(ctx);
+        }
       
         // io: export && import
         if let Some(rx) = self.io_rx.take() {
@@ -124,7 +128,25 @@ impl eframe::App for App {
             self.show_history(ctx);
         }
 
+        if self.state_backups_open {
+            self.show_backups(ctx);
+        }
+
+        if self.state_maintenance_open {
+            self.show_maintenance(ctx);
+        }
+
+        if self.trash_confirm.is_some() {
+            self.show_trash_confirm(ctx);
+        }
+
         if self.state_start {
+            self.check_scheduled_backup();
+
+            // keyboard shortcuts, see keymap.rs
+            // before the panels, so the editor doesn't get the keys
+            self.handle_shortcuts(ctx);
+
             self.show_menubar(ctx);    
             // must be before sidepanels to reserve the space
             self.show_statusbar(ctx);    
@@ -137,22 +159,27 @@ impl eframe::App for App {
                 show_about(ctx, &mut self.show_about);
             }
 
-            // keyboard shortcuts: 
-            // Ctrl+F - search
-            // Ctrl+S - save
-            ctx.input(|i| {
-                if i.key_pressed(Key::F) && i.modifiers.ctrl {
-                    self.state_search = true;
-                    self.search_has_focus = true;
-                } else if i.key_pressed(Key::S) && i.modifiers.ctrl {
-                    let _ = self.try_update_note_content();
-                }
-            });
 
             if self.state_search {
                 self.show_search(ctx);
             }
 
+            if self.state_tasks_open {
+                self.show_tasks(ctx);
+            }
+
+            if self.state_templates_open {
+                self.show_templates(ctx);
+            }
+
+            if self.state_quick_open {
+                self.show_quick_open(ctx);
+            }
+
+            if self.state_command_palette {
+                self.show_command_palette(ctx);
+            }
+
         } else {
             egui::CentralPanel::default().show(ctx, |ui| {
                 ui.vertical_centered(|ui| {
@@ -0,0 +1,163 @@
+//! Rotating backups of the archive
+//! Backups are named <archive name>_<path hash>_<YYYYmmdd_HHMMSS_mmm>.db
+//! and live in Config::backup_dir(). The hash of the full path of the archive
+//! keeps archives with the same file name in different folders apart,
+//! each one lists and prunes only its own backups.
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Receiver;
+use chrono::{Datelike, Local, NaiveDateTime};
+use log::{info, error};
+use crate::config::BackupConfig;
+use crate::db::database::Database;
+
+const TIMESTAMP_FORMAT: &str = "%Y%m%d_%H%M%S_%3f";
+
+#[derive(Debug, Clone)]
+pub struct BackupInfo {
+    pub path: PathBuf,
+    pub created_at: NaiveDateTime,
+    pub note_count: Option<i64>,
+    pub size: u64,
+}
+
+/// "<archive name>_<path hash>_", start of the names of the archive's backups
+fn backup_prefix(db_path: &str) -> String {
+    let path = Path::new(db_path);
+    let stem = path.file_stem()
+        .map(|x| x.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "archive".to_string());
+    let full = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    format!("{stem}_{:08x}_", path_hash(&full.to_string_lossy()))
+}
+
+/// FNV-1a folded to 32 bits, stable across runs and Rust versions
+/// unlike the std hashers
+fn path_hash(path: &str) -> u32 {
+    let hash = path.bytes().fold(0xcbf29ce484222325u64, |hash, b| {
+        (hash ^ b as u64).wrapping_mul(0x100000001b3)
+    });
+    (hash ^ (hash >> 32)) as u32
+}
+
+/// Backup of the archive into dir, old backups are pruned afterwards
+pub fn create_backup(db_path: &str, dir: &Path, config: &BackupConfig) -> Result<PathBuf, String> {
+    let path = write_backup(db_path, dir)?;
+    prune(db_path, dir, config);
+    Ok(path)
+}
+
+fn write_backup(db_path: &str, dir: &Path) -> Result<PathBuf, String> {
+    fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+
+    let name = format!("{}{}.db",
+        backup_prefix(db_path),
+        Local::now().format(TIMESTAMP_FORMAT));
+    let path = dir.join(name);
+
+    let db = Database::new(db_path).map_err(|e| e.to_string())?;
+    db.backup_to(&path).map_err(|e| e.to_string())?;
+    info!("Backup created: {}", path.display());
+    Ok(path)
+}
+
+/// Same as create_backup, but runs in a background thread
+pub fn create_backup_in_background(db_path: &str, dir: PathBuf, config: BackupConfig) {
+    let db_path = db_path.to_string();
+    std::thread::spawn(move || {
+        if let Err(e) = create_backup(&db_path, &dir, &config) {
+            error!("Backup failed: {e}");
+        }
+    });
+}
+
+/// Backups of the archive, newest first
+pub fn list_backups(db_path: &str, dir: &Path) -> Vec<BackupInfo> {
+    let prefix = backup_prefix(db_path);
+    let mut backups = Vec::new();
+
+    if let Ok(entries) = fs::read_dir(dir) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().is_none_or(|ext| ext != "db") {
+                continue;
+            }
+            let created_at = path.file_stem()
+                .and_then(|x| x.to_str())
+                .and_then(|x| x.strip_prefix(&prefix))
+                .and_then(|x| NaiveDateTime::parse_from_str(x, TIMESTAMP_FORMAT).ok());
+
+            if let Some(created_at) = created_at {
+                backups.push(BackupInfo {
+                    path,
+                    created_at,
+                    note_count: None,
+                    size: entry.metadata().map(|m| m.len()).unwrap_or(0),
+                });
+            }
+        }
+    }
+    backups.sort_by_key(|b| std::cmp::Reverse(b.created_at));
+    backups
+}
+
+/// Counts the notes of the backups on a thread, sends (path, count) for each.
+/// Stops when the receiver is dropped.
+pub fn count_notes_in_background(paths: Vec<PathBuf>) -> Receiver<(PathBuf, Option<i64>)> {
+    let (tx, rx) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        for path in paths {
+            let count = path.to_str()
+                .and_then(|x| Database::new(x).ok())
+                .and_then(|db| db.count_notes().ok());
+            if tx.send((path, count)).is_err() {
+                break;
+            }
+        }
+    });
+    rx
+}
+
+/// Keeps the newest backup of each of the last keep_daily days
+/// and of each of the last keep_weekly weeks, the rest is deleted.
+/// The newest backup is always kept.
+pub fn prune(db_path: &str, dir: &Path, config: &BackupConfig) {
+    let backups = list_backups(db_path, dir);
+    let mut days = HashSet::new();
+    let mut weeks = HashSet::new();
+
+    for (i, b) in backups.iter().enumerate() {
+        let day = b.created_at.date();
+        let week = (day.iso_week().year(), day.iso_week().week());
+
+        let mut keep = i == 0;
+        if !days.contains(&day) && days.len() < config.keep_daily as usize {
+            days.insert(day);
+            keep = true;
+        }
+        if !weeks.contains(&week) && weeks.len() < config.keep_weekly as usize {
+            weeks.insert(week);
+            keep = true;
+        }
+
+        if !keep {
+            match fs::remove_file(&b.path) {
+                Ok(_) => info!("Old backup removed: {}", b.path.display()),
+                Err(e) => error!("Failed to remove {}: {e}", b.path.display()),
+            }
+        }
+    }
+}
+
+/// Replaces the archive with the backup.
+/// Current state of the archive is backed up first,
+/// without pruning, so the backup being restored stays in place.
+pub fn restore_backup(db_path: &str, backup: &Path, dir: &Path) -> Result<(), String> {
+    write_backup(db_path, dir)?;
+
+    let mut db = Database::new(db_path).map_err(|e| e.to_string())?;
+    db.restore_from(backup).map_err(|e| e.to_string())?;
+    info!("Archive restored from: {}", backup.display());
+    Ok(())
+}
@@ -0,0 +1,91 @@
+//! Highlighting of fenced code blocks in the preview, bundled syntect syntaxes and themes
+use std::sync::OnceLock;
+use egui::text::{LayoutJob, TextFormat};
+use egui::{Color32, FontId, Stroke, TextStyle};
+use eframe::egui;
+use syntect::easy::HighlightLines;
+use syntect::highlighting::{FontStyle, ThemeSet};
+use syntect::parsing::SyntaxSet;
+use syntect::util::LinesWithEndings;
+
+const THEME_DARK: &str = "base16-ocean.dark";
+const THEME_LIGHT: &str = "InspiredGitHub";
+
+// loading takes a while, done once on the first code block
+fn syntaxes() -> &'static SyntaxSet {
+    static SYNTAXES: OnceLock<SyntaxSet> = OnceLock::new();
+    SYNTAXES.get_or_init(SyntaxSet::load_defaults_newlines)
+}
+
+fn themes() -> &'static ThemeSet {
+    static THEMES: OnceLock<ThemeSet> = OnceLock::new();
+    THEMES.get_or_init(ThemeSet::load_defaults)
+}
+
+/// Background of code blocks for the current theme
+pub fn code_background(dark_mode: bool) -> Color32 {
+    let theme = &themes().themes[if dark_mode { THEME_DARK } else { THEME_LIGHT }];
+    theme.settings.background
+        .map(|c| Color32::from_rgb(c.r, c.g, c.b))
+        .unwrap_or(if dark_mode { Color32::from_gray(30) } else { Color32::from_gray(245) })
+}
+
+/// Highlighted code, cached until the code or the theme changes.
+/// Unknown languages are shown as plain monospace text.
+pub fn highlight_code(ctx: &egui::Context, style: &egui::Style, code: &str, lang: &str) -> LayoutJob {
+    #[derive(Default)]
+    struct Highlighter;
+
+    impl egui::cache::ComputerMut<(&FontId, bool, &str, &str), LayoutJob> for Highlighter {
+        fn compute(&mut self, (font_id, dark_mode, code, lang): (&FontId, bool, &str, &str)) -> LayoutJob {
+            highlight(font_id, dark_mode, code, lang)
+                .unwrap_or_else(|| plain(font_id, dark_mode, code))
+        }
+    }
+
+    type HighlightCache = egui::cache::FrameCache<LayoutJob, Highlighter>;
+
+    let font_id = TextStyle::Monospace.resolve(style);
+    ctx.memory_mut(|mem| {
+        mem.caches
+            .cache::<HighlightCache>()
+            .get((&font_id, style.visuals.dark_mode, code, lang))
+    })
+}
+
+fn highlight(font_id: &FontId, dark_mode: bool, code: &str, lang: &str) -> Option<LayoutJob> {
+    // "rust", "rs", "Rust", "rust,ignore" ...
+    let token = lang.split([',', ' ']).next().unwrap_or_default().trim();
+    if token.is_empty() {
+        return None;
+    }
+    let ps = syntaxes();
+    let syntax = ps.find_syntax_by_token(token)
+        .or_else(|| ps.find_syntax_by_token(&token.to_lowercase()))?;
+    let theme = &themes().themes[if dark_mode { THEME_DARK } else { THEME_LIGHT }];
+
+    let mut h = HighlightLines::new(syntax, theme);
+    let mut job = LayoutJob::default();
+    for line in LinesWithEndings::from(code) {
+        for (style, text) in h.highlight_line(line, ps).ok()? {
+            let fg = style.foreground;
+            job.append(text, 0.0, TextFormat {
+                font_id: font_id.clone(),
+                color: Color32::from_rgb(fg.r, fg.g, fg.b),
+                italics: style.font_style.contains(FontStyle::ITALIC),
+                underline: if style.font_style.contains(FontStyle::UNDERLINE) {
+                    Stroke::new(1.0, Color32::from_rgb(fg.r, fg.g, fg.b))
+                } else {
+                    Stroke::NONE
+                },
+                ..Default::default()
+            });
+        }
+    }
+    Some(job)
+}
+
+fn plain(font_id: &FontId, dark_mode: bool, code: &str) -> LayoutJob {
+    let color = if dark_mode { Color32::LIGHT_GRAY } else { Color32::from_gray(40) };
+    LayoutJob::simple(code.to_string(), font_id.clone(), color, f32::INFINITY)
+}
@@ -1,3 +1,4 @@
+use std::collections::BTreeMap;
 use std::fs;
 use std::io::Write;
 use std::path::PathBuf;
@@ -12,6 +13,34 @@ pub struct Config {
     pub font_size: f32,
     pub is_dark_mode: Option<bool>,
     pub autosave: Option<bool>,
+    pub trash_retention_days: Option<u32>,
+    #[serde(default)]
+    pub backup: BackupConfig,
+    pub keybindings: Option<BTreeMap<String, String>>, // action id -> "Ctrl+Shift+P"
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct BackupConfig {
+    pub enabled: bool,
+    pub on_open: bool,
+    pub dir: Option<PathBuf>, // None - default dir in the user data dir
+    pub interval_hours: u32, // 0 - only on open
+    pub keep_daily: u32, // last N days, one backup per day
+    pub keep_weekly: u32, // last N weeks, one backup per week
+}
+
+impl Default for BackupConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            on_open: true,
+            dir: None,
+            interval_hours: 4,
+            keep_daily: 7,
+            keep_weekly: 4,
+        }
+    }
 }
 
 impl Config {
@@ -26,6 +55,11 @@ impl Config {
             .map(|x| x.to_string_lossy().into_owned())
     }
 
+    pub fn backup_dir(&self) -> Option<PathBuf> {
+        self.backup.dir.clone()
+            .or_else(|| dirs::data_dir().map(|dir| dir.join("nisabo/backups")))
+    }
+
     pub fn load_config() -> Self {
         info!("loading config");
         if let Some(config_path) = Self::get_config_path() {
@@ -56,6 +90,9 @@ impl Config {
                 font_size: self.font_size,
                 is_dark_mode: self.is_dark_mode,
                 autosave: self.autosave,
+                trash_retention_days: self.trash_retention_days,
+                backup: self.backup.clone(),
+                keybindings: self.keybindings.clone(),
             };
             println!("Values: {}", self.font_size);
 
@@ -3,3 +3,5 @@ pub const DEFAULT_IS_DARK_MODE: bool = true;
 pub const DEFAULT_FONT_DIR: &str = "assets/fonts/";
 pub const DEFAULT_FONT: &str = "Default";
 pub const DEFAULT_FONT_SIZE: f32 = 13.0;
+pub const DEFAULT_TRASH_RETENTION_DAYS: u32 = 30; // 0 - keep forever
+pub const ENC_PREFIX: &str = "nisabo:enc:v1:"; // marks encrypted note content
@@ -0,0 +1,84 @@
+//! Per-note encryption
+//! Encrypted content is stored as a single string:
+//! ENC_PREFIX + base64(salt) + ':' + base64(nonce) + ':' + base64(ciphertext)
+//! Key is derived from the passphrase with Argon2id, cipher is ChaCha20-Poly1305.
+use argon2::Argon2;
+use base64::{Engine, engine::general_purpose::STANDARD};
+use chacha20poly1305::{ChaCha20Poly1305, Key, KeyInit, Nonce};
+use chacha20poly1305::aead::{Aead, AeadCore, OsRng, rand_core::RngCore};
+use crate::constants::ENC_PREFIX;
+
+const SALT_LEN: usize = 16;
+
+type Parts = (Vec<u8>, Vec<u8>, Vec<u8>); // salt, nonce, ciphertext
+
+#[derive(Clone)]
+pub struct NoteKey {
+    key: Key,
+    salt: [u8; SALT_LEN],
+}
+
+pub fn is_encrypted(content: &str) -> bool {
+    content.starts_with(ENC_PREFIX)
+}
+
+/// new key with a random salt, used when a note is encrypted for the first time
+pub fn new_key(passphrase: &str) -> Result<NoteKey, String> {
+    let mut salt = [0u8; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    derive_key(passphrase, salt)
+}
+
+/// key for an already encrypted note, salt is taken from the stored content
+pub fn key_for(passphrase: &str, content: &str) -> Result<NoteKey, String> {
+    let (salt, _, _) = split(content)?;
+    let salt: [u8; SALT_LEN] = salt.try_into()
+        .map_err(|_| "Invalid salt".to_string())?;
+    let key = derive_key(passphrase, salt)?;
+    // check the passphrase right away
+    decrypt(&key, content)?;
+    Ok(key)
+}
+
+pub fn encrypt(key: &NoteKey, plaintext: &str) -> Result<String, String> {
+    let cipher = ChaCha20Poly1305::new(&key.key);
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = cipher.encrypt(&nonce, plaintext.as_bytes())
+        .map_err(|e| format!("Encryption failed: {e}"))?;
+
+    Ok(format!("{}{}:{}:{}",
+        ENC_PREFIX,
+        STANDARD.encode(key.salt),
+        STANDARD.encode(nonce),
+        STANDARD.encode(ciphertext)))
+}
+
+pub fn decrypt(key: &NoteKey, content: &str) -> Result<String, String> {
+    let (_, nonce, ciphertext) = split(content)?;
+    if nonce.len() != 12 {
+        return Err("Invalid nonce".to_string());
+    }
+    let cipher = ChaCha20Poly1305::new(&key.key);
+    let plaintext = cipher.decrypt(Nonce::from_slice(&nonce), ciphertext.as_ref())
+        .map_err(|_| "Wrong passphrase".to_string())?;
+    String::from_utf8(plaintext).map_err(|e| e.to_string())
+}
+
+fn derive_key(passphrase: &str, salt: [u8; SALT_LEN]) -> Result<NoteKey, String> {
+    let mut key = Key::default();
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), &salt, &mut key)
+        .map_err(|e| format!("Key derivation failed: {e}"))?;
+    Ok(NoteKey { key, salt })
+}
+
+fn split(content: &str) -> Result<Parts, String> {
+    let body = content.strip_prefix(ENC_PREFIX)
+        .ok_or_else(|| "Note is not encrypted".to_string())?;
+    let parts: Vec<&str> = body.split(':').collect();
+    if parts.len() != 3 {
+        return Err("Invalid encrypted content".to_string());
+    }
+    let decode = |x: &str| STANDARD.decode(x).map_err(|e| e.to_string());
+    Ok((decode(parts[0])?, decode(parts[1])?, decode(parts[2])?))
+}
@@ -1,14 +1,92 @@
-use rusqlite::{Connection, params, Transaction, Result};
-use crate::db::models::{LinkType, Note, NoteIdName, NoteLink, NoteDiff,  NoteLinkIds};
+use rusqlite::{Connection, MAIN_DB, params, Transaction, Result};
+use rusqlite::backup::Progress;
+use std::collections::HashMap;
+use std::path::Path;
+use crate::search_query::SearchQuery;
+use crate::db::models::{LinkType, Note, NoteIdName, NoteDiff, NoteLinkIds, ArchiveStats, TrashItem, SearchResult, SavedSearch, NoteRef, Task, Template, NoteDays};
 
 pub struct Database {
     conn: Connection,
 }
 
+/// Queries saved from the search window, shown as smart folders
+const SAVED_SEARCH_TABLE: &str = "
+CREATE TABLE IF NOT EXISTS saved_search (
+    id              INTEGER PRIMARY KEY AUTOINCREMENT,
+    name            TEXT NOT NULL UNIQUE,
+    query           TEXT NOT NULL,
+    created_at      DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+";
+
+/// Task items of notes, refreshed whenever the note content is written.
+/// Items of encrypted notes are never indexed.
+const TASK_TABLE: &str = "
+CREATE TABLE IF NOT EXISTS task (
+    id              INTEGER PRIMARY KEY AUTOINCREMENT,
+    note_id         INTEGER NOT NULL,
+    position        INTEGER NOT NULL, -- byte offset of '[ ]' in the content
+    text            TEXT NOT NULL,
+    done            INTEGER NOT NULL,
+    due             TEXT, -- YYYY-MM-DD
+    tags            TEXT NOT NULL, -- space separated
+    FOREIGN KEY (note_id) REFERENCES note(id) ON DELETE CASCADE
+);
+CREATE INDEX IF NOT EXISTS task_note_id ON task(note_id);
+";
+
+/// Contents of new notes, see templates.rs
+const TEMPLATE_TABLE: &str = "
+CREATE TABLE IF NOT EXISTS template (
+    id              INTEGER PRIMARY KEY AUTOINCREMENT,
+    name            TEXT NOT NULL UNIQUE,
+    content         TEXT NOT NULL,
+    created_at      DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+";
+
+/// Settings stored in the archive itself, they travel with it
+const SETTING_TABLE: &str = "
+CREATE TABLE IF NOT EXISTS setting (
+    key             TEXT PRIMARY KEY,
+    value           TEXT NOT NULL
+);
+";
+
+/// Keeps note_fts in sync with note.
+/// Content of encrypted notes (starts with ENC_PREFIX) is never indexed,
+/// only their names are.
+const FTS_TRIGGERS: &str = "
+    CREATE TRIGGER IF NOT EXISTS note_ai AFTER INSERT ON note BEGIN
+        INSERT INTO note_fts(rowid, name, content)
+        VALUES (new.id, new.name,
+            CASE WHEN instr(new.content, 'nisabo:enc:') = 1 THEN NULL ELSE new.content END);
+    END;
+
+    CREATE TRIGGER IF NOT EXISTS note_au AFTER UPDATE ON note BEGIN
+        -- delete the old entry
+        INSERT INTO note_fts(note_fts, rowid, name, content)
+        VALUES ('delete', old.id, old.name,
+            CASE WHEN instr(old.content, 'nisabo:enc:') = 1 THEN NULL ELSE old.content END);
+        -- insert the new entry
+        INSERT INTO note_fts(rowid, name, content)
+        VALUES (new.id, new.name,
+            CASE WHEN instr(new.content, 'nisabo:enc:') = 1 THEN NULL ELSE new.content END);
+    END;
+
+    CREATE TRIGGER IF NOT EXISTS note_ad AFTER DELETE ON note BEGIN
+        INSERT INTO note_fts(note_fts, rowid, name, content)
+        VALUES ('delete', old.id, old.name,
+            CASE WHEN instr(old.content, 'nisabo:enc:') = 1 THEN NULL ELSE old.content END);
+    END;
+";
+
 impl Database {
 
     pub fn new(path: &str) -> Result<Self> {
         let conn = Connection::open(path)?;
+        // per connection, without it hard deletes leave dangling links and diffs
+        conn.pragma_update(None, "foreign_keys", "ON")?;
         Ok(Database {conn})
     }
 
@@ -74,33 +152,12 @@ impl Database {
                 content,
                 content='note',
             );
-            
-            CREATE TRIGGER IF NOT EXISTS note_ai AFTER INSERT ON note BEGIN
-                INSERT INTO note_fts(rowid, name, content)
-                VALUES (new.id, new.name, new.content);
-                -- INSERT INTO note_fts(id, name, content)
-                -- VALUES (new.id, new.name, new.content);
-            END;
-
-            CREATE TRIGGER IF NOT EXISTS note_au AFTER UPDATE ON note BEGIN
-                -- delete the old entry
-                INSERT INTO note_fts(note_fts, id, name, content) 
-                VALUES ('delete', old.id, old.name, old.content);
-                -- DELETE FROM note_fts WHERE rowid = old.id;
-                -- insert the new entry
-                INSERT INTO note_fts(rowid, name, content) 
-                VALUES (new.id, new.name, new.content);
-                -- INSERT INTO note_fts(id, name, content) 
-                -- VALUES (new.id, new.name, new.content);
-            END;
-
-            CREATE TRIGGER IF NOT EXISTS note_ad AFTER DELETE ON note BEGIN
-                -- DELETE FROM note_fts WHERE rowid = old.id;
-                INSERT INTO note_fts(note_fts, rowid, name, content) 
-                VALUES ('delete', old.id, old.name, old.content);
-            END;
             ")?;
-
+            tx.execute_batch(FTS_TRIGGERS)?;
+            tx.execute_batch(SAVED_SEARCH_TABLE)?;
+            tx.execute_batch(TASK_TABLE)?;
+            tx.execute_batch(SETTING_TABLE)?;
+            tx.execute_batch(TEMPLATE_TABLE)?;
 
             Ok(())
         });
@@ -112,6 +169,34 @@ impl Database {
         Ok(())
     }
 
+    /// Brings an archive created by an older version up to date.
+    /// Safe to run on every open.
+    pub fn migrate(&mut self) -> Result<()> {
+        let has_tasks: bool = self.conn.query_row(
+            "SELECT COUNT(*) > 0 FROM sqlite_master WHERE type = 'table' AND name = 'task'",
+            [],
+            |row| row.get(0),
+        )?;
+        self.with_transaction(|tx| {
+            tx.execute_batch("
+            DROP TRIGGER IF EXISTS note_ai;
+            DROP TRIGGER IF EXISTS note_au;
+            DROP TRIGGER IF EXISTS note_ad;
+            ")?;
+            tx.execute_batch(FTS_TRIGGERS)?;
+            tx.execute_batch(SAVED_SEARCH_TABLE)?;
+            tx.execute_batch(TASK_TABLE)?;
+            tx.execute_batch(SETTING_TABLE)?;
+            tx.execute_batch(TEMPLATE_TABLE)?;
+            Ok(())
+        })?;
+        // notes saved before the task table existed
+        if !has_tasks {
+            self.rebuild_task_index()?;
+        }
+        Ok(())
+    }
+
     fn insert_dummy_note(&mut self) -> Result<()> {
         self.with_transaction(|tx| {
             tx.execute("
@@ -135,6 +220,7 @@ impl Database {
             ",
             (name, content),
             )?;
+            index_tasks(tx, tx.last_insert_rowid(), content)?;
 
             Ok(())
         })
@@ -142,13 +228,16 @@ impl Database {
 
     pub fn get_notes(&self) -> Result<Vec<NoteIdName>, rusqlite::Error> {
         let mut x = self.conn
-            .prepare("SELECT id, name FROM note WHERE deleted_at is NULL ORDER BY updated_at DESC")?;
+            .prepare("
+                SELECT id, name, COALESCE(instr(content, 'nisabo:enc:') = 1, 0)
+                FROM note WHERE deleted_at is NULL ORDER BY updated_at DESC")?;
         let note_iter = x.query_map([], |row| {
             Ok(NoteIdName {
                 id: row.get(0)?,
                 name: row.get(1)?,
                 children: vec![],
                 has_parent: false,
+                is_encrypted: row.get(2)?,
             })
         })?;
 
@@ -202,57 +291,69 @@ impl Database {
         })
     }
     
-    pub fn get_trash(&self) -> Result<Vec<(i64, String)>> {
-        let mut x = self.conn.prepare("SELECT id, name FROM note WHERE deleted_at IS NOT NULL")?;
+    /// Notes in the trash as a tree, deleted children are grouped
+    /// under their deleted parent
+    pub fn get_trash(&self) -> Result<Vec<TrashItem>> {
+        let mut x = self.conn.prepare("
+            SELECT n.id, n.name, n.deleted_at, l.source_note_id, p.deleted_at IS NOT NULL
+            FROM note n
+            LEFT JOIN note_link l ON l.target_note_id = n.id AND l.link_type = 'parent'
+            LEFT JOIN note p ON p.id = l.source_note_id
+            WHERE n.deleted_at IS NOT NULL
+            ORDER BY n.deleted_at DESC")?;
         let rows = x.query_map([], |row| {
-            Ok((
-                row.get(0)?, 
-                row.get(1)?
-            ))
+            Ok(TrashItem {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                deleted_at: row.get(2)?,
+                parent_id: row.get(3)?,
+                parent_deleted: row.get::<_, Option<bool>>(4)?.unwrap_or(false),
+                children: vec![],
+            })
         })?;
 
         let xz = rows.collect::<Result<Vec<_>,_>>()?;
-        Ok(xz)
+        Ok(build_trash_tree(xz))
     }
    
-    pub fn delete_note_and_children_soft(&mut self, id: i64) -> Result<()> {
-        self.delete_note_soft(id)?;
-        self.delete_note_link_soft(id)?;
-
-        Ok(())
+    /// Parent of the note, the same link get_trash uses for former parents
+    pub fn get_parent_id(&self, id: i64) -> Result<Option<i64>> {
+        let mut stmt = self.conn.prepare("
+            SELECT source_note_id FROM note_link
+            WHERE target_note_id = ?1 AND link_type = 'parent'")?;
+        let mut rows = stmt.query_map(params![id], |row| row.get(0))?;
+        rows.next().transpose()
     }
 
-    fn delete_note_soft(&mut self, id: i64) -> Result<()> {
-        self.with_transaction(|tx| {
-            tx.execute(
-                "UPDATE note SET deleted_at = CURRENT_TIMESTAMP WHERE id = ?1",
-                &[&id],
-            )?;
-            Ok(())
-        })
-    }
-    
-    fn delete_note_link_soft(&mut self, id: i64) -> Result<()> {
+    /// Moves the note with all its children to the trash
+    pub fn delete_note_and_children_soft(&mut self, id: i64) -> Result<()> {
         self.with_transaction(|tx| {
-            tx.execute(
-                "UPDATE note SET deleted_at = CURRENT_TIMESTAMP 
-                WHERE id IN (
-                    SELECT target_note_id
-                    FROM note_link
-                    WHERE source_note_id = ?1 AND link_type = ?2
-                )",
-                params![id, LinkType::Parent.to_string()],
-            )?;
+            for x in subtree_ids(tx, id)? {
+                // children that are already in the trash keep their date
+                tx.execute(
+                    "UPDATE note SET deleted_at = CURRENT_TIMESTAMP 
+                    WHERE id = ?1 AND deleted_at IS NULL",
+                    [x],
+                )?;
+                tx.execute(
+                    "UPDATE note_link SET deleted_at = CURRENT_TIMESTAMP 
+                    WHERE target_note_id = ?1 AND link_type = ?2 AND deleted_at IS NULL",
+                    params![x, LinkType::Parent.to_string()],
+                )?;
+            }
             Ok(())
         })
     }
     
+    /// Permanently deletes the note with all its children in the trash
     pub fn delete_note_hard(&mut self, id: i64) -> Result<()> {
         self.with_transaction(|tx| {
-            tx.execute(
-                "DELETE FROM note WHERE id = ?1",
-                &[&id],
-            )?;
+            for x in subtree_ids(tx, id)? {
+                tx.execute(
+                    "DELETE FROM note WHERE id = ?1 AND deleted_at IS NOT NULL",
+                    [x],
+                )?;
+            }
             Ok(())
         })
     }
@@ -270,25 +371,58 @@ impl Database {
         //Ok(())
     }
     
-    pub fn restore_note(&mut self, id: i64) -> Result<()> {
+    /// Permanently deletes notes that are in the trash for more than days.
+    /// Returns number of deleted notes
+    pub fn purge_trash(&mut self, days: u32) -> Result<usize> {
         self.with_transaction(|tx| {
             tx.execute(
-                "UPDATE note SET deleted_at = NULL WHERE id = ?1",
-                &[&id],
-            )?;
+                "DELETE FROM note 
+                WHERE deleted_at IS NOT NULL 
+                AND deleted_at < datetime('now', ?1)",
+                params![format!("-{} days", days)],
+            )
+        })
+    }
+    
+    /// Restores the note with all its children and their links.
+    /// With to_root the link to the parent is removed,
+    /// used when the parent is still in the trash.
+    pub fn restore_note(&mut self, id: i64, to_root: bool) -> Result<()> {
+        self.with_transaction(|tx| {
+            for x in subtree_ids(tx, id)? {
+                tx.execute(
+                    "UPDATE note SET deleted_at = NULL WHERE id = ?1",
+                    [x],
+                )?;
+                tx.execute(
+                    "UPDATE note_link SET deleted_at = NULL 
+                    WHERE target_note_id = ?1 AND link_type = ?2",
+                    params![x, LinkType::Parent.to_string()],
+                )?;
+            }
+            if to_root {
+                tx.execute(
+                    "DELETE FROM note_link WHERE target_note_id = ?1 AND link_type = ?2",
+                    params![id, LinkType::Parent.to_string()],
+                )?;
+            }
             Ok(())
         })
     }
     
-    pub fn add_new_note(&mut self, name: &str) -> Result<i64> {
+    /// Content is NULL unless the note starts from a template
+    pub fn add_new_note(&mut self, name: &str, content: Option<&str>) -> Result<i64> {
         self.with_transaction(|tx| {
             tx.execute(
-                "INSERT INTO note (name, created_at, updated_at, deleted_at) 
-                VALUES (?1, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP, NULL)",
-                &[&name],
+                "INSERT INTO note (name, content, created_at, updated_at, deleted_at) 
+                VALUES (?1, ?2, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP, NULL)",
+                params![name, content],
             )?;
 
             let id = tx.last_insert_rowid();
+            if let Some(x) = content {
+                index_tasks(tx, id, x)?;
+            }
             Ok(id)
         })
     }
@@ -305,6 +439,45 @@ impl Database {
         })
     }
     
+    /// All notes with their parents, recently updated first
+    pub fn get_note_refs(&self) -> Result<Vec<NoteRef>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT n.id, n.name, l.source_note_id
+            FROM note n
+            LEFT JOIN note_link l ON l.target_note_id = n.id
+                AND l.link_type = 'parent' AND l.deleted_at IS NULL
+            WHERE n.deleted_at IS NULL
+            ORDER BY n.updated_at DESC")?;
+        let iter = stmt.query_map([], |row| {
+            Ok(NoteRef {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                parent_id: row.get(2)?,
+            })
+        })?;
+        iter.collect()
+    }
+
+    /// Notes created or updated between the days (YYYY-MM-DD), both included.
+    /// Timestamps are stored in UTC, the days are local.
+    pub fn get_note_days(&self, from: &str, to: &str) -> Result<Vec<NoteDays>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, name, date(created_at, 'localtime') AS created, date(updated_at, 'localtime') AS updated
+            FROM note
+            WHERE deleted_at IS NULL
+                AND (created BETWEEN ?1 AND ?2 OR updated BETWEEN ?1 AND ?2)
+            ORDER BY name COLLATE NOCASE")?;
+        let iter = stmt.query_map(params![from, to], |row| {
+            Ok(NoteDays {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                created: row.get(2)?,
+                updated: row.get(3)?,
+            })
+        })?;
+        iter.collect()
+    }
+
     pub fn get_note(&self, id: i64) -> Result<Note> {
         self.conn.query_row(
             "SELECT * FROM note WHERE id = ?1",
@@ -328,6 +501,7 @@ impl Database {
                 "UPDATE note SET content = ?1, updated_at = CURRENT_TIMESTAMP WHERE id = ?2",
                 params![new_content, id],
             )?;
+            index_tasks(tx, id, new_content)?;
             Ok(())
         })
     }
@@ -361,28 +535,159 @@ impl Database {
         Ok(notes)
     }
     
-    pub fn search(&self, query: &str) -> Result<Vec<Note>> {
+    /// Full text search, best matches first.
+    /// Matches in name and content are returned as byte ranges,
+    /// content is cut to a short fragment around the matches.
+    pub fn search(&self, query: &SearchQuery) -> Result<Vec<SearchResult>> {
+        let mut predicates = query.predicates.clone();
+        predicates.push(if query.in_trash {
+            "n.deleted_at IS NOT NULL".to_string()
+        } else {
+            "n.deleted_at IS NULL".to_string()
+        });
+        let predicates = predicates.join(" AND ");
+
+        // no text terms => nothing to rank by, newest first
+        let sql = match &query.fts {
+            Some(_) => format!(
+                "SELECT n.id,
+                    highlight(note_fts, 1, char(2), char(3)),
+                    CASE WHEN instr(n.content, 'nisabo:enc:') = 1 THEN ''
+                        ELSE snippet(note_fts, 2, char(2), char(3), '…', 16) END,
+                    n.content
+                FROM note_fts fts
+                JOIN note n ON n.id = fts.rowid
+                WHERE note_fts MATCH ? AND {predicates}
+                ORDER BY rank"),
+            None => format!(
+                "SELECT n.id, n.name,
+                    CASE WHEN instr(n.content, 'nisabo:enc:') = 1 THEN ''
+                        ELSE substr(n.content, 1, 120) END,
+                    n.content
+                FROM note n
+                WHERE {predicates}
+                ORDER BY n.updated_at DESC"),
+        };
+        let mut stmt = self.conn.prepare(&sql)?;
+
+        let iter = stmt.query_map(rusqlite::params_from_iter(query.params.iter()), |row| {
+            let name: String = row.get(1)?;
+            let snippet: Option<String> = row.get(2)?;
+            let content: Option<String> = row.get(3)?;
+            let (name, name_matches) = split_matches(&name);
+            let (snippet, snippet_matches) = split_matches(&snippet.unwrap_or_default());
+            Ok((SearchResult {
+                id: row.get(0)?,
+                name,
+                name_matches,
+                snippet,
+                snippet_matches,
+            }, content.unwrap_or_default()))
+        })?;
+
+        let mut result = Vec::new();
+        for x in iter {
+            let (item, content) = x?;
+            let tags_ok = query.tags.iter()
+                .all(|(tag, negated)| crate::tags::has_tag(&content, tag) != *negated);
+            if tags_ok {
+                result.push(item);
+            }
+        }
+        Ok(result)
+    }
+
+    pub fn get_saved_searches(&self) -> Result<Vec<SavedSearch>> {
         let mut stmt = self.conn.prepare(
-            "SELECT n.id, n.name, n.content
-            FROM note_fts fts 
-            JOIN note n ON n.id = fts.rowid 
-            WHERE note_fts MATCH ?1 
-            AND n.deleted_at IS NULL 
-            ORDER BY rank"
-        )?; 
-        
-        let note_iter = stmt.query_map(params![query], |row| {
-            Ok(Note {
+            "SELECT id, name, query FROM saved_search ORDER BY name COLLATE NOCASE")?;
+        let iter = stmt.query_map([], |row| {
+            Ok(SavedSearch {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                query: row.get(2)?,
+            })
+        })?;
+        iter.collect()
+    }
+
+    /// Saving under an existing name replaces its query
+    pub fn save_search(&mut self, name: &str, query: &str) -> Result<()> {
+        self.with_transaction(|tx| {
+            tx.execute("
+            INSERT INTO saved_search (name, query) VALUES (?1, ?2)
+            ON CONFLICT(name) DO UPDATE SET query = excluded.query
+            ",
+            params![name, query],
+            )?;
+            Ok(())
+        })
+    }
+
+    pub fn delete_saved_search(&mut self, id: i64) -> Result<()> {
+        self.with_transaction(|tx| {
+            tx.execute("DELETE FROM saved_search WHERE id = ?1", params![id])?;
+            Ok(())
+        })
+    }
+
+    pub fn get_templates(&self) -> Result<Vec<Template>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, name, content FROM template ORDER BY name COLLATE NOCASE")?;
+        let iter = stmt.query_map([], |row| {
+            Ok(Template {
                 id: row.get(0)?,
                 name: row.get(1)?,
                 content: row.get(2)?,
-                created_at: String::new(),
-                updated_at: String::new(),
-                deleted_at: None,
             })
         })?;
-        
-        note_iter.collect()
+        iter.collect()
+    }
+
+    /// Updates the template with id, without one saving under an existing
+    /// name replaces its content. Returns the id of the saved template.
+    pub fn save_template(&mut self, id: Option<i64>, name: &str, content: &str) -> Result<i64> {
+        self.with_transaction(|tx| {
+            if let Some(id) = id {
+                tx.execute(
+                    "UPDATE template SET name = ?1, content = ?2 WHERE id = ?3",
+                    params![name, content, id],
+                )?;
+                return Ok(id);
+            }
+            tx.query_row("
+            INSERT INTO template (name, content) VALUES (?1, ?2)
+            ON CONFLICT(name) DO UPDATE SET content = excluded.content
+            RETURNING id
+            ",
+            params![name, content],
+            |row| row.get(0),
+            )
+        })
+    }
+
+    pub fn delete_template(&mut self, id: i64) -> Result<()> {
+        self.with_transaction(|tx| {
+            tx.execute("DELETE FROM template WHERE id = ?1", params![id])?;
+            Ok(())
+        })
+    }
+
+    pub fn get_setting(&self, key: &str) -> Result<Option<String>> {
+        let mut stmt = self.conn.prepare("SELECT value FROM setting WHERE key = ?1")?;
+        let mut rows = stmt.query_map(params![key], |row| row.get(0))?;
+        rows.next().transpose()
+    }
+
+    pub fn set_setting(&mut self, key: &str, value: &str) -> Result<()> {
+        self.with_transaction(|tx| {
+            tx.execute("
+            INSERT INTO setting (key, value) VALUES (?1, ?2)
+            ON CONFLICT(key) DO UPDATE SET value = excluded.value
+            ",
+            params![key, value],
+            )?;
+            Ok(())
+        })
     }
 
     // draft
@@ -445,6 +750,48 @@ impl Database {
         )
     }
     
+    /// Full diffs of the note, used to re-encrypt its history
+    pub fn select_note_diff_all(&mut self, note_id: i64) -> Result<Vec<NoteDiff>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, version, diff, changed_at 
+            FROM note_diff 
+            WHERE note_id = ?1 
+            ORDER BY version"
+        )?;
+        let note_diff_iter = stmt.query_map(params![note_id], |row| {
+            Ok(NoteDiff {
+                id: row.get(0)?,
+                version: row.get(1)?,
+                diff: row.get(2)?,
+                changed_at: row.get(3)?,
+            })
+        })?;
+        note_diff_iter.collect()
+    }
+
+    /// Replaces content and history of the note in one go.
+    /// Used to encrypt or decrypt a note, so updated_at is left untouched.
+    pub fn replace_note_content_and_diffs(
+        &mut self,
+        id: i64,
+        content: &str,
+        diffs: &[(i64, String)]) -> Result<()> {
+        self.with_transaction(|tx| {
+            tx.execute(
+                "UPDATE note SET content = ?1 WHERE id = ?2",
+                params![content, id],
+            )?;
+            index_tasks(tx, id, content)?;
+            for (diff_id, diff) in diffs {
+                tx.execute(
+                    "UPDATE note_diff SET diff = ?1 WHERE id = ?2 AND note_id = ?3",
+                    params![diff, diff_id, id],
+                )?;
+            }
+            Ok(())
+        })
+    }
+    
     fn select_latest_note_diff_version(&mut self, note_id: i64) -> Result<i64> {
         self.conn.query_row(
             "SELECT version 
@@ -455,4 +802,241 @@ impl Database {
             |row| row.get(0),
         )
     }
+
+    /// Copy of the whole archive using SQLite online backup API
+    pub fn backup_to(&self, path: &Path) -> Result<()> {
+        self.conn.backup(MAIN_DB, path, None)
+    }
+
+    /// Replaces the whole archive with the content of a backup
+    pub fn restore_from(&mut self, path: &Path) -> Result<()> {
+        self.conn.restore(MAIN_DB, path, None::<fn(Progress)>)
+    }
+
+    pub fn count_notes(&self) -> Result<i64> {
+        self.conn.query_row(
+            "SELECT COUNT(*) FROM note WHERE deleted_at IS NULL",
+            [],
+            |row| row.get(0),
+        )
+    }
+
+    /// "ok" if there are no problems
+    pub fn integrity_check(&self) -> Result<Vec<String>> {
+        let mut stmt = self.conn.prepare("PRAGMA integrity_check")?;
+        let rows = stmt.query_map([], |row| row.get(0))?;
+        rows.collect()
+    }
+
+    /// Rows that violate foreign keys, empty if there are no problems
+    pub fn foreign_key_check(&self) -> Result<Vec<String>> {
+        let mut stmt = self.conn.prepare("PRAGMA foreign_key_check")?;
+        let rows = stmt.query_map([], |row| {
+            let table: String = row.get(0)?;
+            let rowid: Option<i64> = row.get(1)?;
+            let parent: String = row.get(2)?;
+            Ok(format!("{}: row {} references missing {}",
+                table,
+                rowid.map(|x| x.to_string()).unwrap_or("?".to_string()),
+                parent))
+        })?;
+        rows.collect()
+    }
+
+    pub fn rebuild_fts(&mut self) -> Result<()> {
+        self.with_transaction(|tx| {
+            tx.execute("INSERT INTO note_fts(note_fts) VALUES('rebuild')", [])?;
+            // rebuild reads note.content as is, take content of encrypted notes
+            // out of the index again, the same way the triggers do
+            tx.execute("
+                INSERT INTO note_fts(note_fts, rowid, name, content)
+                SELECT 'delete', id, name, content FROM note
+                WHERE instr(content, 'nisabo:enc:') = 1",
+                [],
+            )?;
+            tx.execute("
+                INSERT INTO note_fts(rowid, name, content)
+                SELECT id, name, NULL FROM note
+                WHERE instr(content, 'nisabo:enc:') = 1",
+                [],
+            )?;
+            Ok(())
+        })
+    }
+
+    /// Indexes task items of all notes again, returns number of items
+    pub fn rebuild_task_index(&mut self) -> Result<usize> {
+        let notes = self.get_all_notes()?;
+        self.with_transaction(|tx| {
+            tx.execute("DELETE FROM task", [])?;
+            let mut count = 0;
+            for note in &notes {
+                count += index_tasks(tx, note.id, note.content.as_deref().unwrap_or_default())?;
+            }
+            Ok(count)
+        })
+    }
+
+    /// Task items of notes that are not in the trash, by note
+    pub fn get_tasks(&self) -> Result<Vec<Task>> {
+        let mut stmt = self.conn.prepare("
+            SELECT t.note_id, n.name, t.position, t.text, t.done, t.due, t.tags
+            FROM task t
+            JOIN note n ON n.id = t.note_id
+            WHERE n.deleted_at IS NULL
+            ORDER BY n.updated_at DESC, t.note_id, t.position")?;
+        let iter = stmt.query_map([], |row| {
+            let tags: String = row.get(6)?;
+            Ok(Task {
+                note_id: row.get(0)?,
+                note_name: row.get(1)?,
+                position: row.get(2)?,
+                text: row.get(3)?,
+                done: row.get(4)?,
+                due: row.get(5)?,
+                tags: tags.split_whitespace().map(String::from).collect(),
+            })
+        })?;
+        iter.collect()
+    }
+
+    /// Removes links and diffs of notes that no longer exist.
+    /// Returns number of removed (links, diffs)
+    pub fn remove_orphans(&mut self) -> Result<(usize, usize)> {
+        self.with_transaction(|tx| {
+            let links = tx.execute("
+                DELETE FROM note_link
+                WHERE source_note_id NOT IN (SELECT id FROM note)
+                OR target_note_id NOT IN (SELECT id FROM note)",
+                [],
+            )?;
+            let diffs = tx.execute("
+                DELETE FROM note_diff
+                WHERE note_id NOT IN (SELECT id FROM note)",
+                [],
+            )?;
+            Ok((links, diffs))
+        })
+    }
+
+    pub fn vacuum(&self) -> Result<()> {
+        self.conn.execute_batch("VACUUM")
+    }
+
+    pub fn stats(&self) -> Result<ArchiveStats> {
+        self.conn.query_row("
+            SELECT
+                (SELECT COUNT(*) FROM note WHERE deleted_at IS NULL),
+                (SELECT COUNT(*) FROM note WHERE deleted_at IS NOT NULL),
+                (SELECT COUNT(*) FROM note_diff),
+                (SELECT COALESCE(SUM(length(diff)), 0) FROM note_diff),
+                (SELECT page_count * page_size FROM pragma_page_count(), pragma_page_size())",
+            [],
+            |row| {
+                Ok(ArchiveStats {
+                    note_count: row.get(0)?,
+                    trash_count: row.get(1)?,
+                    history_count: row.get(2)?,
+                    history_size: row.get(3)?,
+                    db_size: row.get(4)?,
+                })
+            },
+        )
+    }
+}
+
+/// Removes match markers (char 2 and 3) added by highlight() and snippet(),
+/// returns text without markers and byte ranges of the matches
+fn split_matches(marked: &str) -> (String, Vec<std::ops::Range<usize>>) {
+    let mut text = String::with_capacity(marked.len());
+    let mut matches = Vec::new();
+    let mut start = None;
+
+    for c in marked.chars() {
+        match c {
+            '\u{2}' => start = Some(text.len()),
+            '\u{3}' => {
+                if let Some(x) = start.take() {
+                    matches.push(x..text.len());
+                }
+            }
+            _ => text.push(c),
+        }
+    }
+    (text, matches)
+}
+
+/// Ids of the note and all its descendants
+fn subtree_ids(conn: &Connection, id: i64) -> Result<Vec<i64>> {
+    let mut stmt = conn.prepare("
+        WITH RECURSIVE subtree(id) AS (
+            SELECT ?1
+            UNION
+            SELECT l.target_note_id FROM note_link l
+            JOIN subtree s ON l.source_note_id = s.id
+            WHERE l.link_type = ?2
+        )
+        SELECT id FROM subtree")?;
+    let rows = stmt.query_map(params![id, LinkType::Parent.to_string()], |row| row.get(0))?;
+    rows.collect()
+}
+
+/// Items with a deleted parent go to the children of that parent,
+/// order of the items is kept
+fn build_trash_tree(items: Vec<TrashItem>) -> Vec<TrashItem> {
+    let ids: Vec<i64> = items.iter().map(|x| x.id).collect();
+    let mut by_id: HashMap<i64, TrashItem> = items
+        .into_iter()
+        .map(|x| (x.id, x))
+        .collect();
+
+    fn take(id: i64, by_id: &mut HashMap<i64, TrashItem>, ids: &[i64]) -> Option<TrashItem> {
+        let mut item = by_id.remove(&id)?;
+        for child_id in ids {
+            let is_child = by_id.get(child_id)
+                .is_some_and(|x| x.parent_deleted && x.parent_id == Some(id));
+            if is_child && let Some(child) = take(*child_id, by_id, ids) {
+                item.children.push(child);
+            }
+        }
+        Some(item)
+    }
+
+    let roots: Vec<i64> = ids.iter()
+        .copied()
+        .filter(|id| by_id.get(id).is_some_and(|x| {
+            !x.parent_deleted || x.parent_id.is_none_or(|p| !by_id.contains_key(&p))
+        }))
+        .collect();
+
+    let mut tree: Vec<TrashItem> = roots.into_iter()
+        .filter_map(|id| take(id, &mut by_id, &ids))
+        .collect();
+    // cycles can't be reached from a root, show them anyway
+    for id in ids {
+        if let Some(x) = by_id.remove(&id) {
+            tree.push(x);
+        }
+    }
+    tree
+}
+
+/// Replaces indexed task items of the note, returns number of items
+fn index_tasks(tx: &Transaction, note_id: i64, content: &str) -> Result<usize> {
+    tx.execute("DELETE FROM task WHERE note_id = ?1", [note_id])?;
+    if crate::crypto::is_encrypted(content) {
+        return Ok(0);
+    }
+    let tasks = crate::tasks::extract_tasks(content);
+    let note_tags = crate::tasks::note_tags(content, &tasks);
+    for x in &tasks {
+        let mut tags = x.tags.clone();
+        tags.extend(note_tags.iter().filter(|t| !x.tags.contains(t)).cloned());
+        tx.execute(
+            "INSERT INTO task (note_id, position, text, done, due, tags)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![note_id, x.position, x.text, x.done, x.due, tags.join(" ")],
+        )?;
+    }
+    Ok(tasks.len())
 }
@@ -16,6 +16,70 @@ pub struct NoteIdName {
     pub name: String,
     pub children: Vec<NoteIdName>,
     pub has_parent: bool,
+    pub is_encrypted: bool,
+}
+
+/// Flat list entry for quick open, newest first
+#[derive(Debug, Clone)]
+pub struct NoteRef {
+    pub id: i64,
+    pub name: String,
+    pub parent_id: Option<i64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct TrashItem {
+    pub id: i64,
+    pub name: String,
+    pub deleted_at: String,
+    pub parent_id: Option<i64>,
+    pub parent_deleted: bool,
+    pub children: Vec<TrashItem>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SearchResult {
+    pub id: i64,
+    pub name: String,
+    pub name_matches: Vec<std::ops::Range<usize>>, // byte ranges in name
+    pub snippet: String, // fragment of the content around the matches
+    pub snippet_matches: Vec<std::ops::Range<usize>>, // byte ranges in snippet
+}
+
+#[derive(Debug, Clone)]
+pub struct SavedSearch {
+    pub id: i64,
+    pub name: String,
+    pub query: String,
+}
+
+/// Content of new notes with variables, see templates.rs
+#[derive(Debug, Clone)]
+pub struct Template {
+    pub id: i64,
+    pub name: String,
+    pub content: String,
+}
+
+/// Days a note was created and last updated on, in local time, see calendar.rs
+#[derive(Debug, Clone)]
+pub struct NoteDays {
+    pub id: i64,
+    pub name: String,
+    pub created: String, // YYYY-MM-DD
+    pub updated: String, // YYYY-MM-DD
+}
+
+/// Indexed task item with its note, see tasks.rs
+#[derive(Debug, Clone)]
+pub struct Task {
+    pub note_id: i64,
+    pub note_name: String,
+    pub position: usize, // of "[ ]" in the note content
+    pub text: String,
+    pub done: bool,
+    pub due: Option<String>, // YYYY-MM-DD
+    pub tags: Vec<String>, // of the item and of its note
 }
 
 #[derive(Debug)]
@@ -49,6 +113,15 @@ pub struct NoteDiff {
     pub changed_at: String,
 }
 
+#[derive(Debug, Default, Clone)]
+pub struct ArchiveStats {
+    pub note_count: i64,
+    pub trash_count: i64,
+    pub history_count: i64,
+    pub history_size: i64, // bytes
+    pub db_size: i64, // bytes
+}
+
 impl std::str::FromStr for LinkType {
     type Err = ();
 
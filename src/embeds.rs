@@ -0,0 +1,201 @@
+//! Notes embedded in other notes, "![[Note Name]]" or "![[Note Name#Heading]]"
+//! on lines of their own. The preview draws the embedded note in place,
+//! the html export puts its source in place of the embed.
+use std::collections::HashMap;
+use pulldown_cmark::{Event, Options, Parser, Tag};
+use crate::db::models::NoteIdName;
+
+const MAX_DEPTH: usize = 4; // of embeds inside embedded notes
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Embed {
+    pub name: String,
+    pub heading: Option<String>, // only this section of the note
+}
+
+impl Embed {
+    pub fn title(&self) -> String {
+        match &self.heading {
+            Some(heading) => format!("{}#{heading}", self.name),
+            None => self.name.clone(),
+        }
+    }
+}
+
+/// Embeds of a paragraph when all of its lines are embeds
+pub fn embed_targets(paragraph: &str) -> Option<Vec<Embed>> {
+    paragraph.lines()
+        .map(str::trim)
+        .filter(|x| !x.is_empty())
+        .map(|line| {
+            let inner = line.strip_prefix("![[")?.strip_suffix("]]")?;
+            if inner.contains(['[', ']']) {
+                return None;
+            }
+            // "![[Note|shown text]]"
+            let target = inner.split('|').next().unwrap_or_default();
+            let (name, heading) = match target.split_once('#') {
+                Some((name, heading)) => (name, Some(heading.trim().to_string())),
+                None => (target, None),
+            };
+            let name = name.trim();
+            (!name.is_empty()).then(|| Embed { name: name.to_string(), heading })
+        })
+        .collect::<Option<Vec<_>>>()
+        .filter(|x| !x.is_empty())
+}
+
+/// Section of the heading up to the next heading of the same or a higher level,
+/// the heading is given by its title or its anchor
+pub fn section<'a>(content: &'a str, heading: &str, options: Options) -> Option<&'a str> {
+    let outline = crate::markdown::outline(content, options);
+    let i = outline.iter().position(|x| {
+        x.title.trim().eq_ignore_ascii_case(heading) || x.anchor == crate::markdown::slug(heading)
+    })?;
+    let level = outline[i].level;
+    let end = outline[i + 1..].iter()
+        .find(|x| x.level <= level)
+        .map_or(content.len(), |x| x.offset);
+    Some(&content[outline[i].offset..end])
+}
+
+/// Content of the note with the name, Err with why it can't be embedded
+type Load<'a> = Box<dyn FnMut(&str) -> Result<String, String> + 'a>;
+
+/// Where embedded notes come from, keeps track of the chain of
+/// embeds being drawn to stop cycles
+pub struct Embeds<'a> {
+    load: Load<'a>,
+    chain: Vec<String>, // names, starting with the note itself
+}
+
+impl<'a> Embeds<'a> {
+    /// Load gives the content of the note with the name
+    pub fn new(note: Option<&str>, load: impl FnMut(&str) -> Result<String, String> + 'a) -> Self {
+        Embeds {
+            load: Box::new(load),
+            chain: note.map(|x| vec![x.to_string()]).unwrap_or_default(),
+        }
+    }
+
+    /// Content to show for the embed, Err with what's wrong with it
+    pub fn resolve(&mut self, embed: &Embed, options: Options) -> Result<String, String> {
+        if self.chain.iter().any(|x| x.eq_ignore_ascii_case(&embed.name)) {
+            return Err(format!("Embed cycle: {} → {}", self.chain.join(" → "), embed.name));
+        }
+        if self.chain.len() > MAX_DEPTH {
+            return Err(format!("Embeds are nested more than {MAX_DEPTH} deep"));
+        }
+        let content = (self.load)(&embed.name)?;
+        match &embed.heading {
+            Some(heading) => section(&content, heading, options)
+                .map(str::to_string)
+                .ok_or_else(|| format!("No heading \"{heading}\" in {}", embed.name)),
+            None => Ok(content),
+        }
+    }
+
+    /// Content of the embed is drawn or expanded between enter and leave
+    pub fn enter(&mut self, embed: &Embed) {
+        self.chain.push(embed.name.clone());
+    }
+
+    pub fn leave(&mut self) {
+        self.chain.pop();
+    }
+
+    /// Source with the embeds replaced by the embedded notes, for the export.
+    /// Problems are left in place as quotes.
+    pub fn expand(&mut self, md: &str, options: Options) -> String {
+        let mut out = String::new();
+        let mut pos = 0;
+        for (event, range) in Parser::new_ext(md, options).into_offset_iter() {
+            let Event::Start(Tag::Paragraph) = event else {
+                continue;
+            };
+            let Some(embeds) = embed_targets(&md[range.clone()]) else {
+                continue;
+            };
+            // keep inserted lines inside the list item or quote
+            let line_start = md[..range.start].rfind('\n').map_or(0, |x| x + 1);
+            let indent: String = md[line_start..range.start].chars()
+                .map(|c| if c == '>' || c.is_whitespace() { c } else { ' ' })
+                .collect();
+
+            out.push_str(&md[pos..range.start]);
+            let mut text = String::new();
+            for embed in &embeds {
+                let part = match self.resolve(embed, options) {
+                    Ok(content) => {
+                        self.enter(embed);
+                        let expanded = self.expand(&content, options);
+                        self.leave();
+                        expanded
+                    }
+                    Err(e) => format!("> ⚠ {e}"),
+                };
+                text.push_str(part.trim_end());
+                text.push_str("\n\n");
+            }
+            let text = text.trim_end().replace('\n', &format!("\n{indent}"));
+            out.push_str(&text);
+            out.push('\n');
+            pos = range.end;
+        }
+        out.push_str(&md[pos..]);
+        out
+    }
+}
+
+/// Contents of embedded notes of the preview by name, loaded on first use.
+/// Cleared whenever notes are saved or reloaded.
+#[derive(Default)]
+pub struct EmbedCache {
+    contents: HashMap<String, Result<String, String>>,
+}
+
+impl EmbedCache {
+    pub fn clear(&mut self) {
+        self.contents.clear();
+    }
+
+    /// Saved content of the note with the name, encrypted notes are never embedded
+    pub fn load(&mut self, db_path: &str, names: &[NoteIdName], name: &str) -> Result<String, String> {
+        self.contents
+            .entry(name.to_lowercase())
+            .or_insert_with(|| {
+                let id = find_note(names, name).ok_or(format!("No note named \"{name}\""))?;
+                let note = crate::db::database::Database::new(db_path)
+                    .and_then(|db| db.get_note(id))
+                    .map_err(|e| format!("Error loading {name}: {e}"))?;
+                let content = note.content.unwrap_or_default();
+                if crate::crypto::is_encrypted(&content) {
+                    return Err(format!("🔒 {name} is encrypted"));
+                }
+                Ok(content)
+            })
+            .clone()
+    }
+}
+
+/// Id of the note with the name anywhere in the tree, case insensitive
+pub fn find_note(names: &[NoteIdName], name: &str) -> Option<i64> {
+    names.iter().find_map(|x| {
+        if x.name.trim().eq_ignore_ascii_case(name.trim()) {
+            Some(x.id)
+        } else {
+            find_note(&x.children, name)
+        }
+    })
+}
+
+/// Name of the note with the id anywhere in the tree
+pub fn note_name(names: &[NoteIdName], id: i64) -> Option<&str> {
+    names.iter().find_map(|x| {
+        if x.id == id {
+            Some(x.name.as_str())
+        } else {
+            note_name(&x.children, id)
+        }
+    })
+}
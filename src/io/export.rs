@@ -5,8 +5,10 @@ use std::io::Write;
 use std::error::Error;
 use log::{info, error};
 use crate::app::{App, IoOperation, ProgressState};
-use pulldown_cmark::{Parser, Options, html};
+use pulldown_cmark::{Event, Parser, Options, html};
 use crate::db::models::Note;
+use crate::embeds::Embeds;
+use std::collections::HashMap;
 
 impl App {
     pub fn export(&mut self, target: &str) -> Result<(), Box<dyn Error>> {
@@ -31,6 +33,12 @@ impl App {
                 let notes = db.get_all_notes()?;
                 let total = notes.len().max(1); // prevent division by 0
                 let format = target.to_string(); // to fix borrow issue
+                let options = self.markdown_extensions.0;
+                // notes that can be embedded by name, encrypted ones are never shown
+                let contents: HashMap<String, String> = notes.iter()
+                    .filter_map(|x| Some((x.name.trim().to_lowercase(), x.content.clone()?)))
+                    .filter(|(_, content)| !crate::crypto::is_encrypted(content))
+                    .collect();
 
                 self.io_operation = Some(IoOperation::Export);
                 let handle = std::thread::spawn(move || -> Result<usize, String> {                    
@@ -53,7 +61,14 @@ impl App {
                         
                         let data = format_note_as_md(&note);
                             let output = match format.as_str() {
-                                "html" => md_to_html(&data),
+                                "html" => {
+                                    let mut embeds = Embeds::new(Some(&note.name), |name| {
+                                        contents.get(&name.trim().to_lowercase())
+                                            .cloned()
+                                            .ok_or(format!("No note named \"{name}\""))
+                                    });
+                                    md_to_html(&embeds.expand(&data, options), options)
+                                }
                                 _ => data,
                             };
                             match file.write_all(output.as_bytes()) {
@@ -142,10 +157,13 @@ fn sanitize(s: &str) -> String {
     x
 }
 
-fn md_to_html(md: &str) -> String {
-    let options = Options::empty();
-
-    let parser = Parser::new_ext(md, options);
+/// Formulas become MathML, see math::to_mathml
+fn md_to_html(md: &str, options: Options) -> String {
+    let parser = Parser::new_ext(md, options).map(|event| match event {
+        Event::InlineMath(tex) => Event::InlineHtml(crate::math::to_mathml(&tex, false).into()),
+        Event::DisplayMath(tex) => Event::InlineHtml(crate::math::to_mathml(&tex, true).into()),
+        event => event,
+    });
 
     let mut html_output = String::new();
     html::push_html(&mut html_output, parser);
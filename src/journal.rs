@@ -0,0 +1,63 @@
+//! Daily journal notes, named by their date ("2026-10-19") under a top level
+//! parent note. The parent and the template of new entries are settings of
+//! the archive, the parent is made when the first entry is.
+use std::collections::HashMap;
+use chrono::NaiveDate;
+use crate::db::database::Database;
+use crate::db::models::NoteIdName;
+
+pub const PARENT_SETTING: &str = "journal_parent";
+pub const TEMPLATE_SETTING: &str = "journal_template";
+const DEFAULT_PARENT: &str = "Journal";
+const NAME_FORMAT: &str = "%Y-%m-%d";
+
+#[derive(Debug, Clone)]
+pub struct JournalSettings {
+    pub parent: String, // name of the parent note
+    pub template: String, // name of the template of new entries, empty for none
+}
+
+impl Default for JournalSettings {
+    fn default() -> Self {
+        JournalSettings {
+            parent: DEFAULT_PARENT.to_string(),
+            template: String::new(),
+        }
+    }
+}
+
+impl JournalSettings {
+    pub fn load(db: &Database) -> rusqlite::Result<Self> {
+        let parent = db.get_setting(PARENT_SETTING)?
+            .filter(|x| !x.trim().is_empty())
+            .unwrap_or(DEFAULT_PARENT.to_string());
+        let template = db.get_setting(TEMPLATE_SETTING)?.unwrap_or_default();
+        Ok(JournalSettings { parent, template })
+    }
+
+    pub fn save(&self, db: &mut Database) -> rusqlite::Result<()> {
+        db.set_setting(PARENT_SETTING, self.parent.trim())?;
+        db.set_setting(TEMPLATE_SETTING, &self.template)
+    }
+}
+
+pub fn entry_name(date: NaiveDate) -> String {
+    date.format(NAME_FORMAT).to_string()
+}
+
+/// Top level note with the name, case insensitive
+pub fn parent_note<'a>(names: &'a [NoteIdName], parent: &str) -> Option<&'a NoteIdName> {
+    names.iter().find(|x| !x.has_parent && x.name.trim().eq_ignore_ascii_case(parent.trim()))
+}
+
+/// Ids of the entries under the parent by their date, other children are skipped
+pub fn entries(names: &[NoteIdName], parent: &str) -> HashMap<NaiveDate, i64> {
+    parent_note(names, parent)
+        .map(|x| x.children.iter()
+            .filter_map(|child| {
+                let date = NaiveDate::parse_from_str(child.name.trim(), NAME_FORMAT).ok()?;
+                Some((date, child.id))
+            })
+            .collect())
+        .unwrap_or_default()
+}
@@ -0,0 +1,127 @@
+//! Keyboard shortcuts of actions, defaults + overrides from config.toml:
+//!
+//! [keybindings]
+//! new_note = "Ctrl+Alt+N"
+//! history = ""            # no shortcut
+//!
+//! Ctrl is Cmd on macOS.
+use std::collections::BTreeMap;
+use eframe::egui::{Key, KeyboardShortcut, Modifiers};
+use log::{warn};
+use crate::actions::Action;
+
+#[derive(Default)]
+pub struct Keymap {
+    bindings: Vec<(Action, KeyboardShortcut)>, // longer chords first
+    pub conflicts: Vec<(String, Vec<Action>)>, // chord, actions bound to it
+    pub errors: Vec<(Action, String)>,
+}
+
+impl Keymap {
+    pub fn new(overrides: &Option<BTreeMap<String, String>>) -> Self {
+        let mut keymap = Keymap::default();
+        let mut by_chord: BTreeMap<String, Vec<Action>> = BTreeMap::new();
+
+        if let Some(x) = overrides {
+            for id in x.keys() {
+                if Action::from_id(id).is_none() {
+                    warn!("Unknown action in keybindings: {id}");
+                }
+            }
+        }
+
+        for action in Action::ALL {
+            let chord = binding(overrides, action);
+            if chord.is_empty() {
+                continue;
+            }
+            match parse_shortcut(&chord) {
+                Ok(shortcut) => {
+                    by_chord.entry(format_shortcut(&shortcut)).or_default().push(action);
+                    keymap.bindings.push((action, shortcut));
+                }
+                Err(e) => keymap.errors.push((action, e)),
+            }
+        }
+
+        // a chord bound to several actions runs none of them
+        for (chord, actions) in by_chord {
+            if actions.len() > 1 {
+                warn!("Shortcut {chord} is bound to {:?}", actions);
+                keymap.bindings.retain(|(x, _)| !actions.contains(x));
+                keymap.conflicts.push((chord, actions));
+            }
+        }
+
+        // Ctrl+P would also match Ctrl+Shift+P, check longer chords first
+        keymap.bindings.sort_by_key(|(_, s)| std::cmp::Reverse(modifier_count(s.modifiers)));
+        keymap
+    }
+
+    pub fn bindings(&self) -> &[(Action, KeyboardShortcut)] {
+        &self.bindings
+    }
+
+    pub fn shortcut(&self, action: Action) -> Option<KeyboardShortcut> {
+        self.bindings.iter()
+            .find(|(x, _)| *x == action)
+            .map(|(_, s)| *s)
+    }
+
+    pub fn is_conflict(&self, action: Action) -> bool {
+        self.conflicts.iter().any(|(_, x)| x.contains(&action))
+    }
+}
+
+/// Chord of the action, as written in config.toml
+pub fn binding(overrides: &Option<BTreeMap<String, String>>, action: Action) -> String {
+    overrides.as_ref()
+        .and_then(|x| x.get(action.id()))
+        .cloned()
+        .unwrap_or_else(|| action.default_binding().unwrap_or_default().to_string())
+}
+
+/// "Ctrl+Shift+P" -> shortcut
+pub fn parse_shortcut(chord: &str) -> Result<KeyboardShortcut, String> {
+    let mut modifiers = Modifiers::NONE;
+    let mut key = None;
+    for part in chord.split('+').map(|x| x.trim()) {
+        match part.to_lowercase().as_str() {
+            "ctrl" | "control" | "cmd" | "command" => modifiers |= Modifiers::COMMAND,
+            "shift" => modifiers |= Modifiers::SHIFT,
+            "alt" | "option" => modifiers |= Modifiers::ALT,
+            "" => return Err(format!("Invalid shortcut '{chord}'")),
+            _ => {
+                if key.is_some() {
+                    return Err(format!("Only one key is allowed in '{chord}'"));
+                }
+                key = Some(Key::from_name(part)
+                    .or_else(|| Key::from_name(&part.to_uppercase()))
+                    .ok_or(format!("Unknown key '{part}'"))?);
+            }
+        }
+    }
+    let key = key.ok_or(format!("Missing key in '{chord}'"))?;
+    Ok(KeyboardShortcut::new(modifiers, key))
+}
+
+/// Shortcut -> "Ctrl+Shift+P"
+pub fn format_shortcut(shortcut: &KeyboardShortcut) -> String {
+    let m = shortcut.modifiers;
+    let mut parts = Vec::new();
+    if m.command || m.ctrl || m.mac_cmd {
+        parts.push("Ctrl");
+    }
+    if m.shift {
+        parts.push("Shift");
+    }
+    if m.alt {
+        parts.push("Alt");
+    }
+    parts.push(shortcut.logical_key.name());
+    parts.join("+")
+}
+
+fn modifier_count(m: Modifiers) -> u8 {
+    m.alt as u8 + m.shift as u8 + (m.command || m.ctrl || m.mac_cmd) as u8
+}
@@ -5,11 +5,24 @@ mod ui;
 mod db;
 mod metadata;
 mod markdown;
+mod markdown_highlight;
+mod code_highlight;
 mod io;
 mod constants;
 mod utils;
 mod font;
 mod diff;
+mod crypto;
+mod backup;
+mod tags;
+mod tasks;
+mod math;
+mod embeds;
+mod templates;
+mod journal;
+mod search_query;
+mod actions;
+mod keymap;
 use app::App;
 
 fn main() -> Result<(), eframe::Error> {
@@ -1,249 +1,1027 @@
-use pulldown_cmark::{LinkType, Parser, Event, Tag, TagEnd};
-use egui::{Color32, Context, OpenUrl, RichText, FontId, TextStyle};
+//! Markdown preview. Events of pulldown_cmark are collected into blocks
+//! (paragraphs, lists, quotes, ...) holding inline spans, then the blocks
+//! are drawn. Nested blocks are drawn recursively with their indentation.
+use std::collections::HashMap;
+use std::iter::Peekable;
+use std::ops::Range;
+use pulldown_cmark::{Alignment, BlockQuoteKind, CodeBlockKind, Event, LinkType, Options, Parser, Tag, TagEnd};
+use egui::{Color32, FontFamily, FontId, Stroke, TextStyle};
 use egui::text::{LayoutJob, TextFormat};
 use eframe::egui;
+use crate::embeds::{Embed, Embeds, embed_targets};
+use crate::math::Formula;
 
-/*
- * TODO:
- * You can refactor this as you wish.
- * Parse md to RichText using pulldown_cmark and egui
- */ 
-#[derive(Default)]
-struct RenderState {
-    is_paragraph: bool,
-    is_list_item: bool,
-    is_blockquote: bool,
-    is_code: bool,
-    is_bold: bool,
-    is_italic: bool,
-    list_prefix: Option<String>,
-    ordered_index: usize,
-    heading_level: Option<u32>,
+const BLOCK_SPACING: f32 = 7.0;
+const LIST_INDENT: f32 = 22.0; // width of bullets and numbers
+const BULLETS: [&str; 3] = ["•", "◦", "▪"]; // by nesting depth
+const NOTE_LINK_PREFIX: &str = "note:"; // links of [[Note]], followed to the note
+
+/// Key of the enabled extensions in the setting table of the archive
+pub const EXTENSIONS_SETTING: &str = "markdown_extensions";
+
+/// Extensions that can be enabled per archive: (id stored in the archive, title, options)
+pub const EXTENSIONS: [(&str, &str, Options); 13] = [
+    ("tables", "Tables", Options::ENABLE_TABLES),
+    ("tasklists", "Task lists - [ ]", Options::ENABLE_TASKLISTS),
+    ("strikethrough", "Strikethrough ~~text~~", Options::ENABLE_STRIKETHROUGH),
+    ("footnotes", "Footnotes [^1]", Options::ENABLE_FOOTNOTES),
+    ("smart_punctuation", "Smart punctuation", Options::ENABLE_SMART_PUNCTUATION),
+    ("heading_attributes", "Heading attributes {#id}", Options::ENABLE_HEADING_ATTRIBUTES),
+    ("definition_lists", "Definition lists", Options::ENABLE_DEFINITION_LIST),
+    ("superscript", "Superscript ^text^", Options::ENABLE_SUPERSCRIPT),
+    ("subscript", "Subscript ~text~", Options::ENABLE_SUBSCRIPT),
+    ("math", "Math $x^2$", Options::ENABLE_MATH),
+    ("alerts", "Alerts > [!NOTE]", Options::ENABLE_GFM),
+    ("wikilinks", "Wiki links [[Note]]", Options::ENABLE_WIKILINKS),
+    ("metadata", "Metadata blocks ---",
+        Options::ENABLE_YAML_STYLE_METADATA_BLOCKS.union(Options::ENABLE_PLUSES_DELIMITED_METADATA_BLOCKS)),
+];
+
+/// Extensions of archives that have no setting yet
+const DEFAULT_EXTENSIONS: Options = Options::ENABLE_TABLES
+    .union(Options::ENABLE_TASKLISTS)
+    .union(Options::ENABLE_STRIKETHROUGH)
+    .union(Options::ENABLE_FOOTNOTES)
+    .union(Options::ENABLE_HEADING_ATTRIBUTES)
+    .union(Options::ENABLE_DEFINITION_LIST)
+    .union(Options::ENABLE_MATH)
+    .union(Options::ENABLE_GFM);
+
+/// Extensions enabled in an archive
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Extensions(pub Options);
+
+impl Default for Extensions {
+    fn default() -> Self {
+        Self(DEFAULT_EXTENSIONS)
+    }
+}
+
+impl Extensions {
+    /// From the setting value, "tables footnotes ...", unknown ids are ignored
+    pub fn parse(value: &str) -> Self {
+        Self(value.split_whitespace()
+            .filter_map(|id| EXTENSIONS.iter().find(|(x, _, _)| *x == id))
+            .fold(Options::empty(), |acc, (_, _, options)| acc | *options))
+    }
+
+    /// Setting value, see parse
+    pub fn to_setting(self) -> String {
+        EXTENSIONS.iter()
+            .filter(|(_, _, x)| self.0.contains(*x))
+            .map(|(id, _, _)| *id)
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// Text run with the same formatting
+#[derive(Debug, Clone)]
+struct Span {
+    text: String,
+    style: SpanStyle,
+    kind: SpanKind,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+enum SpanKind {
+    #[default]
+    Text,
+    Math { display: bool }, // text is the TeX
+    FootnoteRef,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+struct SpanStyle {
+    strong: bool,
+    emphasis: bool,
+    code: bool,
+    strikethrough: bool,
+    superscript: bool,
+    subscript: bool,
     link: Option<String>,
 }
 
-pub fn render_md(ui: &mut egui::Ui, ctx: &egui::Context, md: &str) {
-    let parser = Parser::new(md);
-    let mut buffer = String::new();
-
-    let mut state = RenderState {
-        is_paragraph: false,
-        is_list_item: false,
-        is_blockquote: false,
-        is_code: false,
-        is_bold: false,
-        is_italic: false,
-        list_prefix: None,
-        ordered_index: 1,
-        heading_level: None,
-        link: None,
+#[derive(Debug)]
+enum Block {
+    Paragraph(Vec<Span>),
+    Text(Vec<Span>), // text of a tight list item, without paragraph spacing
+    Heading { level: u32, spans: Vec<Span>, anchor: String },
+    Quote { kind: Option<BlockQuoteKind>, blocks: Blocks },
+    Code { lang: String, code: String },
+    List { start: Option<u64>, items: Vec<ListItem> },
+    Table { alignments: Vec<Alignment>, rows: Vec<Vec<Vec<Span>>> }, // first row is the header
+    Definitions(Vec<(Vec<Span>, Vec<Blocks>)>), // terms with their definitions
+    Footnotes(Vec<Footnote>), // section at the end of the note
+    Embeds(Vec<Embed>), // paragraph of "![[Note]]" lines
+    Rule,
+}
+
+/// Blocks with the source offsets where they start
+type Blocks = Vec<(usize, Block)>;
+
+#[derive(Debug)]
+struct ListItem {
+    task: Option<(bool, usize)>, // checked, offset of "[ ]" in the source
+    blocks: Blocks,
+}
+
+#[derive(Debug)]
+struct Footnote {
+    number: usize,
+    label: String,
+    blocks: Blocks,
+}
+
+/// Heading of the note, see outline
+#[derive(Debug, Clone)]
+pub struct OutlineEntry {
+    pub level: u32,
+    pub title: String,
+    pub anchor: String,
+    pub offset: usize, // in the source
+}
+
+/// What the preview reports back after drawing a note
+#[derive(Default)]
+pub struct MdResponse {
+    pub toggled_task: Option<usize>, // offset of a clicked checkbox, see toggle_task
+    pub opened_note: Option<String>, // name of a clicked [[Note]] link
+    pub source_map: SourceMap,
+}
+
+/// Source offsets of blocks and their positions in the preview,
+/// used to keep the preview in sync with the editor
+#[derive(Debug, Clone, Default)]
+pub struct SourceMap {
+    blocks: Vec<(usize, f32)>, // in document order
+}
+
+impl SourceMap {
+    /// Preview position of the source offset, between the blocks around it
+    pub fn y(&self, offset: usize) -> f32 {
+        let i = self.blocks.partition_point(|(start, _)| *start <= offset);
+        let Some(&(start, y)) = i.checked_sub(1).and_then(|x| self.blocks.get(x)) else {
+            return 0.0;
+        };
+        match self.blocks.get(i) {
+            Some(&(next_start, next_y)) if next_start > start => {
+                let t = (offset - start) as f32 / (next_start - start) as f32;
+                y + (next_y - y) * t
+            }
+            _ => y,
+        }
+    }
+}
+
+pub fn render_md(ui: &mut egui::Ui, ctx: &egui::Context, md: &str, options: Options, embeds: Embeds) -> MdResponse {
+    let blocks = parse_blocks(md, options);
+    let mut renderer = Renderer {
+        ctx: ctx.clone(),
+        options,
+        list_depth: 0,
+        table_count: 0,
+        toggled_task: None,
+        opened_note: None,
+        scroll_to: ctx.data_mut(|d| d.remove_temp::<String>(scroll_id())),
+        source_map: SourceMap::default(),
+        origin: ui.min_rect().top(),
+        embeds,
+        embed_depth: 0,
     };
+    renderer.draw_blocks(ui, &blocks, false);
+    MdResponse {
+        toggled_task: renderer.toggled_task,
+        opened_note: renderer.opened_note,
+        source_map: renderer.source_map,
+    }
+}
 
-    let mut layout_job = LayoutJob::default();
-    
-    for event in parser {
+/// Blocks of the note with the footnote section at the end
+fn parse_blocks(md: &str, options: Options) -> Blocks {
+    let mut parser = BlockParser {
+        events: Parser::new_ext(md, options).into_offset_iter().peekable(),
+        source: md,
+        task: None,
+        slugs: Slugs::default(),
+        title: String::new(),
+        footnotes: Vec::new(),
+        footnote_numbers: HashMap::new(),
+    };
+    let mut blocks = parser.blocks();
+    if let Some(footnotes) = parser.footnotes() {
+        blocks.push((md.len(), footnotes));
+    }
+    blocks
+}
+
+/// Flips "[ ]" and "[x]" of the task at the offset given by render_md
+pub fn toggle_task(md: &mut String, offset: usize) {
+    let mark = match md.get(offset..offset + 3) {
+        Some("[ ]") => "x",
+        Some("[x]") | Some("[X]") => " ",
+        _ => return,
+    };
+    md.replace_range(offset + 1..offset + 2, mark);
+}
+
+/// Headings with their anchors, the same ones the preview uses
+pub fn outline(md: &str, options: Options) -> Vec<OutlineEntry> {
+    let mut entries: Vec<OutlineEntry> = Vec::new();
+    let mut slugs = Slugs::default();
+    let mut heading: Option<(OutlineEntry, Option<String>)> = None; // with the explicit id
+    for (event, range) in Parser::new_ext(md, options).into_offset_iter() {
         match event {
-            Event::Start(tag) => start_tag(tag, &mut state, &mut buffer, &mut layout_job),
-            Event::End(tag) => end_tag(tag, &mut state, ui, &mut buffer, &layout_job),
-            Event::Text(text) => event_text(&text, &mut state, ui, ctx, &mut buffer, &mut layout_job),
-            Event::Code(code) => {
-                ui.label(
-                    RichText::new(code.as_ref())
-                        .monospace()
-                        .background_color(egui::Color32::DARK_GRAY)
-                        .color(egui::Color32::LIGHT_GRAY),
-                );
-            },
-            Event::Rule => { ui.separator(); },
-            Event::SoftBreak | Event::HardBreak if state.is_paragraph => {
-                //ui.allocate_exact_size(vec2(0.0, 12.0), Sense::hover());
-                //ui.end_row();
-                layout_job.append("\n", 0.0, TextFormat::default());
-            },
-            _ => {}
-        }
-    }
-}
-
-fn start_tag(tag: Tag, state: &mut RenderState, buffer: &mut String, layout_job: &mut LayoutJob) {
-    match tag {
-        Tag::Paragraph => {
-            state.is_paragraph = true;
-            *layout_job = LayoutJob::default();
-        }
-        Tag::BlockQuote(_) => {
-            state.is_blockquote = true;
-            buffer.clear();
-        }
-        Tag::Heading {level, .. } => {
-            state.heading_level = Some(level as u32);
-            buffer.clear();
-        }
-        Tag::CodeBlock(_) => {
-            state.is_code = true;
-            buffer.clear();
-        }
-        Tag::List(Some(start)) => {
-            state.ordered_index = start as usize;
-        }
-        Tag::List(None) => {
-            state.ordered_index = 0;
-        }
-        Tag::Item => {
-            state.is_list_item = true;
-            state.list_prefix = Some(if state.ordered_index == 0 {
-                "- ".to_string()
-            } else {
-                let prefix = format!("{}. ", state.ordered_index);
-                state.ordered_index += 1;
-                prefix
-            });
+            Event::Start(Tag::Heading { level, id, .. }) => {
+                let entry = OutlineEntry {
+                    level: level as u32,
+                    title: String::new(),
+                    anchor: String::new(),
+                    offset: range.start,
+                };
+                heading = Some((entry, id.map(|x| x.to_string())));
+            }
+            Event::End(TagEnd::Heading(_)) => {
+                if let Some((mut entry, id)) = heading.take() {
+                    entry.anchor = id.unwrap_or_else(|| slugs.anchor(&entry.title));
+                    entries.push(entry);
+                }
+            }
+            event => {
+                if let Some((entry, _)) = &mut heading
+                    && let Some(text) = heading_text(&event) {
+                    entry.title.push_str(text);
+                }
+            }
         }
-        Tag::Strong => {
-            state.is_bold = true;
+    }
+    entries
+}
+
+/// Part of the title of a heading the event adds, the outline and the
+/// preview build the anchors of headings from the same titles
+fn heading_text<'e>(event: &'e Event) -> Option<&'e str> {
+    match event {
+        Event::Text(text) | Event::Code(text) => Some(text),
+        Event::SoftBreak | Event::HardBreak => Some(" "),
+        _ => None,
+    }
+}
+
+/// Scrolls the preview to the heading with the anchor on the next frame
+pub fn scroll_to_anchor(ctx: &egui::Context, anchor: &str) {
+    ctx.data_mut(|d| d.insert_temp(scroll_id(), anchor.to_string()));
+}
+
+fn scroll_id() -> egui::Id {
+    egui::Id::new("md_scroll_to_anchor")
+}
+
+/// Anchor of the footnote, can't clash with headings as slugs have no ':'
+fn footnote_anchor(label: &str) -> String {
+    format!("fn:{label}")
+}
+
+/// GitHub style anchor, "Some Title!" -> "some-title"
+pub fn slug(title: &str) -> String {
+    title.trim()
+        .to_lowercase()
+        .chars()
+        .filter_map(|c| match c {
+            ' ' => Some('-'),
+            c if c.is_alphanumeric() || c == '-' || c == '_' => Some(c),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Anchors of the headings of a note, repeated titles get "-1", "-2", ...
+#[derive(Default)]
+struct Slugs {
+    used: HashMap<String, usize>,
+}
+
+impl Slugs {
+    fn anchor(&mut self, title: &str) -> String {
+        let slug = slug(title);
+        let count = self.used.entry(slug.clone()).or_insert(0);
+        let anchor = if *count == 0 { slug } else { format!("{slug}-{count}") };
+        *count += 1;
+        anchor
+    }
+}
+
+struct BlockParser<'a, I: Iterator<Item = (Event<'a>, Range<usize>)>> {
+    events: Peekable<I>,
+    source: &'a str,
+    task: Option<(bool, usize)>, // marker of the current list item
+    slugs: Slugs,
+    title: String, // heading_text of the last spans
+    footnotes: Vec<(String, Blocks)>, // definitions by label
+    footnote_numbers: HashMap<String, usize>, // by the first reference
+}
+
+impl<'a, I: Iterator<Item = (Event<'a>, Range<usize>)>> BlockParser<'a, I> {
+    /// Blocks until the end of the enclosing quote or list item
+    fn blocks(&mut self) -> Blocks {
+        let mut blocks = Vec::new();
+        while let Some((event, range)) = self.events.next() {
+            let block = match event {
+                Event::Start(Tag::Paragraph) => {
+                    let spans = self.spans(None);
+                    match embed_targets(&self.source[range.clone()]) {
+                        Some(embeds) => Block::Embeds(embeds),
+                        None => Block::Paragraph(spans),
+                    }
+                }
+                Event::Start(Tag::Heading { level, id, .. }) => {
+                    let spans = self.spans(None);
+                    let anchor = match id {
+                        Some(x) => x.to_string(),
+                        None => self.slugs.anchor(&self.title),
+                    };
+                    Block::Heading { level: level as u32, spans, anchor }
+                }
+                Event::Start(Tag::BlockQuote(kind)) => Block::Quote { kind, blocks: self.blocks() },
+                Event::Start(Tag::CodeBlock(kind)) => self.code_block(kind),
+                Event::Start(Tag::List(start)) => self.list(start),
+                Event::Start(Tag::Table(alignments)) => self.table(alignments),
+                Event::Start(Tag::DefinitionList) => self.definitions(),
+                Event::Start(Tag::FootnoteDefinition(label)) => {
+                    let blocks = self.blocks();
+                    self.footnotes.push((label.to_string(), blocks));
+                    continue;
+                }
+                Event::Rule => Block::Rule,
+                Event::TaskListMarker(checked) => {
+                    self.task = Some((checked, range.start));
+                    continue;
+                }
+                Event::End(_) => break,
+                // tight list items have no paragraphs
+                event if is_inline(&event) => Block::Text(self.spans(Some(event))),
+                Event::Start(_) => {
+                    self.skip();
+                    continue;
+                }
+                _ => continue,
+            };
+            blocks.push((range.start, block));
         }
-        Tag::Emphasis => { // italic
-            state.is_italic = true;
+        blocks
+    }
+
+    /// Skips a block that isn't drawn, e.g. raw html
+    fn skip(&mut self) {
+        let mut depth = 1;
+        for (event, _) in self.events.by_ref() {
+            match event {
+                Event::Start(_) => depth += 1,
+                Event::End(_) => {
+                    depth -= 1;
+                    if depth == 0 {
+                        break;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Spans until the end of the paragraph, heading or table cell.
+    /// With first, spans of a tight list item until the next block.
+    fn spans(&mut self, first: Option<Event<'a>>) -> Vec<Span> {
+        let tight = first.is_some();
+        let mut spans: Vec<Span> = Vec::new();
+        let mut style = SpanStyle::default();
+        let mut next = first.map(|x| (x, 0..0));
+        self.title.clear();
+        loop {
+            let (event, range) = match next.take() {
+                Some(x) => x,
+                None if tight => match self.events.next_if(|(x, _)| is_inline(x)) {
+                    Some(x) => x,
+                    None => break,
+                },
+                None => match self.events.next() {
+                    Some(x) => x,
+                    None => break,
+                },
+            };
+            if let Some(text) = heading_text(&event) {
+                self.title.push_str(text);
+            }
+            match event {
+                Event::Text(text) => push_span(&mut spans, &text, &style),
+                Event::Code(code) => push_span(&mut spans, &code, &SpanStyle { code: true, ..style.clone() }),
+                Event::SoftBreak | Event::HardBreak => push_span(&mut spans, "\n", &style),
+                // loose list items, the marker is inside the paragraph
+                Event::TaskListMarker(checked) => self.task = Some((checked, range.start)),
+                Event::Start(Tag::Strong) => style.strong = true,
+                Event::End(TagEnd::Strong) => style.strong = false,
+                Event::Start(Tag::Emphasis) => style.emphasis = true,
+                Event::End(TagEnd::Emphasis) => style.emphasis = false,
+                Event::Start(Tag::Strikethrough) => style.strikethrough = true,
+                Event::End(TagEnd::Strikethrough) => style.strikethrough = false,
+                Event::Start(Tag::Superscript) => style.superscript = true,
+                Event::End(TagEnd::Superscript) => style.superscript = false,
+                Event::Start(Tag::Subscript) => style.subscript = true,
+                Event::End(TagEnd::Subscript) => style.subscript = false,
+                Event::InlineMath(tex) => spans.push(math_span(&tex, &style, false)),
+                Event::DisplayMath(tex) => spans.push(math_span(&tex, &style, true)),
+                Event::FootnoteReference(label) => {
+                    let number = self.footnote_number(&label);
+                    let reference = SpanStyle {
+                        superscript: true,
+                        link: Some(format!("#{}", footnote_anchor(&label))),
+                        ..style.clone()
+                    };
+                    spans.push(Span { text: format!("[{number}]"), style: reference, kind: SpanKind::FootnoteRef });
+                }
+                Event::Start(Tag::Link { link_type, dest_url, .. }) => style.link = Some(link_target(link_type, &dest_url)),
+                Event::Start(Tag::Image { link_type, dest_url, .. }) => {
+                    style.link = Some(link_target(link_type, &dest_url));
+                    push_span(&mut spans, "🖼 ", &style);
+                }
+                Event::End(TagEnd::Link) | Event::End(TagEnd::Image) => style.link = None,
+                Event::End(_) => break,
+                _ => {}
+            }
         }
-        Tag::Link {link_type: LinkType::Inline, dest_url, ..} => {
-            state.link = Some(dest_url.to_string());
+        spans
+    }
+
+    fn code_block(&mut self, kind: CodeBlockKind) -> Block {
+        let mut code = String::new();
+        for (event, _) in self.events.by_ref() {
+            match event {
+                Event::Text(text) => code.push_str(&text),
+                _ => break, // End(CodeBlock)
+            }
         }
-        _ => {}
+        let lang = match kind {
+            CodeBlockKind::Fenced(lang) => lang.to_string(),
+            CodeBlockKind::Indented => String::new(),
+        };
+        Block::Code { lang, code: code.trim_end_matches('\n').to_string() }
     }
-}
 
-fn end_tag(
-    tag: TagEnd,
-    state: &mut RenderState,
-    ui: &mut egui::Ui,
-    buffer: &mut String,
-    layout_job: &LayoutJob) {
-    let b = buffer.as_str();
-    match tag {
-        TagEnd::Paragraph => {
-            state.is_paragraph = false;
-            ui.label(layout_job.clone());
-            ui.add_space(7.0);
+    fn list(&mut self, start: Option<u64>) -> Block {
+        let mut items = Vec::new();
+        while let Some((Event::Start(Tag::Item), _)) = self.events.next() {
+            // a nested item must not take the marker of its parent
+            let parent_task = self.task.take();
+            let blocks = self.blocks();
+            let task = std::mem::replace(&mut self.task, parent_task);
+            items.push(ListItem { task, blocks });
         }
-        TagEnd::Heading(_) => {
-            if let Some(level) = state.heading_level.take() {
-                let rt = match level {
-                    1 => RichText::new(b).heading(),
-                    2 => RichText::new(b).strong().size(22.0),
-                    3 => RichText::new(b).strong().size(18.0),
-                    _ => RichText::new(b).size(16.0),
-                };
-                ui.label(rt);
-                buffer.clear();
+        Block::List { start, items }
+    }
+
+    fn table(&mut self, alignments: Vec<Alignment>) -> Block {
+        let mut rows: Vec<Vec<Vec<Span>>> = Vec::new();
+        while let Some((event, _)) = self.events.next() {
+            match event {
+                Event::Start(Tag::TableHead) | Event::Start(Tag::TableRow) => rows.push(Vec::new()),
+                Event::Start(Tag::TableCell) => {
+                    let cell = self.spans(None);
+                    if let Some(row) = rows.last_mut() {
+                        row.push(cell);
+                    }
+                }
+                Event::End(TagEnd::Table) => break,
+                _ => {}
             }
         }
-        TagEnd::BlockQuote(_) => {
-            state.is_blockquote = false;
-            ui.add_space(7.0);
-            egui::Frame::new()
-                .fill(Color32::LIGHT_GRAY)
-                .inner_margin(7.0)
-                .show(ui, |ui| {
-                    ui.label(RichText::new(buffer.as_str().trim_end())
-                             .italics()
-                             .color(Color32::DARK_GRAY));
-                });
-            buffer.clear();
-        }
-        TagEnd::CodeBlock => {
-            ui.label(
-                RichText::new(b)
-                    .monospace()
-                    .background_color(egui::Color32::DARK_GRAY)
-                    .color(egui::Color32::LIGHT_GRAY),
-            );
-            buffer.clear();
-            state.is_code = false;
-        }
-        TagEnd::Item => {
-            state.is_list_item = false;
-            state.list_prefix = None;
-        }
-        TagEnd::Strong => { state.is_bold = false; }
-        TagEnd::Emphasis => { state.is_italic = false; }
-        TagEnd::Link => { state.link = None; }
-        _ => {}
-    }
-}
-
-fn event_text(
-    text: &str,
-    state: &mut RenderState,
-    ui: &mut egui::Ui,
-    ctx: &egui::Context,
-    buffer: &mut String,
-    layout_job: &mut LayoutJob) {
-        if let Some(link) = &state.link {
-            if link.starts_with('#') {
-                let anchor = &link[1..];
-
-                if ui.link(text).clicked() {
-                    println!("Clicked on anchor link to: {}", anchor);
-                }
-            } else {
-                if ui.link(text).clicked() {
-                    ctx.open_url(egui::OpenUrl {
-                        url: link.to_string(),
-                        new_tab: true,
-                    });
-                    println!("Clicked on external link to: {}", link);
+        Block::Table { alignments, rows }
+    }
+
+    fn definitions(&mut self) -> Block {
+        let mut items: Vec<(Vec<Span>, Vec<Blocks>)> = Vec::new();
+        while let Some((event, _)) = self.events.next() {
+            match event {
+                Event::Start(Tag::DefinitionListTitle) => items.push((self.spans(None), Vec::new())),
+                Event::Start(Tag::DefinitionListDefinition) => {
+                    let blocks = self.blocks();
+                    match items.last_mut() {
+                        Some((_, definitions)) => definitions.push(blocks),
+                        None => items.push((Vec::new(), vec![blocks])),
+                    }
+                }
+                Event::End(TagEnd::DefinitionList) => break,
+                _ => {}
+            }
+        }
+        Block::Definitions(items)
+    }
+
+    fn footnote_number(&mut self, label: &str) -> usize {
+        let next = self.footnote_numbers.len() + 1;
+        *self.footnote_numbers.entry(label.to_string()).or_insert(next)
+    }
+
+    /// Section of the collected definitions in the order of their numbers,
+    /// definitions that are never referenced come last
+    fn footnotes(&mut self) -> Option<Block> {
+        if self.footnotes.is_empty() {
+            return None;
+        }
+        let mut footnotes: Vec<Footnote> = std::mem::take(&mut self.footnotes)
+            .into_iter()
+            .map(|(label, blocks)| Footnote { number: self.footnote_number(&label), label, blocks })
+            .collect();
+        footnotes.sort_by_key(|x| x.number);
+        Some(Block::Footnotes(footnotes))
+    }
+}
+
+/// Where a click on the link goes, [[Note]] links open the note
+fn link_target(link_type: LinkType, dest_url: &str) -> String {
+    match link_type {
+        LinkType::WikiLink { .. } => format!("{NOTE_LINK_PREFIX}{dest_url}"),
+        _ => dest_url.to_string(),
+    }
+}
+
+fn is_inline(event: &Event) -> bool {
+    match event {
+        Event::Text(_) | Event::Code(_) | Event::SoftBreak | Event::HardBreak
+            | Event::InlineMath(_) | Event::DisplayMath(_) | Event::FootnoteReference(_) => true,
+        Event::Start(tag) => matches!(tag,
+            Tag::Strong | Tag::Emphasis | Tag::Strikethrough | Tag::Superscript | Tag::Subscript
+            | Tag::Link { .. } | Tag::Image { .. }),
+        Event::End(tag) => matches!(tag,
+            TagEnd::Strong | TagEnd::Emphasis | TagEnd::Strikethrough | TagEnd::Superscript
+            | TagEnd::Subscript | TagEnd::Link | TagEnd::Image),
+        _ => false,
+    }
+}
+
+fn math_span(tex: &str, style: &SpanStyle, display: bool) -> Span {
+    Span { text: tex.to_string(), style: style.clone(), kind: SpanKind::Math { display } }
+}
+
+/// Appends to the last span when the style is the same
+fn push_span(spans: &mut Vec<Span>, text: &str, style: &SpanStyle) {
+    match spans.last_mut() {
+        Some(last) if last.kind == SpanKind::Text && last.style == *style => last.text.push_str(text),
+        _ => spans.push(Span { text: text.to_string(), style: style.clone(), kind: SpanKind::Text }),
+    }
+}
+
+struct Renderer<'a> {
+    ctx: egui::Context,
+    options: Options,
+    list_depth: usize,
+    table_count: usize, // for unique grid ids
+    toggled_task: Option<usize>,
+    opened_note: Option<String>,
+    scroll_to: Option<String>, // anchor, see scroll_to_anchor
+    source_map: SourceMap,
+    origin: f32, // top of the preview
+    embeds: Embeds<'a>,
+    embed_depth: usize, // blocks of other notes are drawn when > 0
+}
+
+impl Renderer<'_> {
+    fn draw_blocks(&mut self, ui: &mut egui::Ui, blocks: &[(usize, Block)], quote: bool) {
+        for (start, block) in blocks {
+            self.source_map.blocks.push((*start, ui.cursor().top() - self.origin));
+            match block {
+                Block::Paragraph(spans) => {
+                    self.draw_spans(ui, spans, &body_format(ui, quote));
+                    ui.add_space(BLOCK_SPACING);
+                }
+                Block::Text(spans) => {
+                    self.draw_spans(ui, spans, &body_format(ui, quote));
+                }
+                Block::Heading { level, spans, anchor } => {
+                    let size = match level {
+                        1 => 26.0,
+                        2 => 22.0,
+                        3 => 18.0,
+                        _ => 16.0,
+                    };
+                    let format = TextFormat {
+                        font_id: FontId::proportional(size),
+                        color: ui.visuals().strong_text_color(),
+                        ..body_format(ui, quote)
+                    };
+                    let response = self.draw_spans(ui, spans, &format);
+                    if self.scroll_to.as_ref() == Some(anchor) {
+                        response.scroll_to_me(Some(egui::Align::TOP));
+                        self.scroll_to = None;
+                    }
+                    ui.add_space(BLOCK_SPACING);
+                }
+                Block::Quote { kind, blocks } => self.draw_quote(ui, *kind, blocks),
+                Block::Code { lang, code } => code_block(ui, code, lang),
+                Block::List { start, items } => {
+                    self.draw_list(ui, *start, items, quote);
+                    if self.list_depth == 0 {
+                        ui.add_space(BLOCK_SPACING);
+                    }
+                }
+                Block::Table { alignments, rows } => {
+                    self.draw_table(ui, alignments, rows);
+                    self.table_count += 1;
+                }
+                Block::Definitions(items) => self.draw_definitions(ui, items, quote),
+                Block::Footnotes(footnotes) => self.draw_footnotes(ui, footnotes),
+                Block::Embeds(embeds) => {
+                    for embed in embeds {
+                        self.draw_embed(ui, embed);
+                    }
+                }
+                Block::Rule => {
+                    ui.separator();
                 }
             }
-            
-            return;
-        }
-
-        if state.is_blockquote {
-            buffer.push_str(&text);
-            buffer.push('\n');
-        } else if state.is_code {
-            buffer.push_str(&text);
-        } else if state.heading_level.is_some() {
-            buffer.push_str(&text);
-        } else if state.is_paragraph {
-            let style = ui.style();
-            let font_id = if state.is_bold {
-                style.text_styles.get(&TextStyle::Heading).cloned()
-            } else {
-                style.text_styles.get(&TextStyle::Body).cloned()
+        }
+    }
+
+    /// Wrapped text with clickable links
+    fn draw_spans(&mut self, ui: &mut egui::Ui, spans: &[Span], base: &TextFormat) -> egui::Response {
+        if spans.iter().any(|x| matches!(x.kind, SpanKind::Math { .. })) {
+            return self.draw_math_spans(ui, spans, base);
+        }
+        let (job, links) = layout_spans(ui, spans, base);
+        let (response, link) = rich_label(ui, job, &links);
+        if let Some(link) = link {
+            self.open_link(&link);
+        }
+        response
+    }
+
+    /// Text with formulas, inline ones flow with the text
+    /// and display ones get a line of their own
+    fn draw_math_spans(&mut self, ui: &mut egui::Ui, spans: &[Span], base: &TextFormat) -> egui::Response {
+        ui.horizontal_wrapped(|ui| {
+            ui.spacing_mut().item_spacing.x = 0.0;
+            let mut start = 0;
+            let mut line_empty = true; // an empty line would add its height
+            for (i, span) in spans.iter().enumerate() {
+                let SpanKind::Math { display } = span.kind else {
+                    continue;
+                };
+                line_empty &= !self.draw_flow(ui, &spans[start..i], base);
+                start = i + 1;
+
+                let size = if display { base.font_id.size * 1.15 } else { base.font_id.size };
+                match Formula::new(ui, &span.text, size, base.color, display) {
+                    Ok(formula) => {
+                        let mut size = formula.size();
+                        if display {
+                            if !line_empty {
+                                ui.end_row();
+                            }
+                            size.x = ui.available_width();
+                        }
+                        let (rect, _) = ui.allocate_exact_size(size, egui::Sense::hover());
+                        formula.paint(ui.painter(), rect);
+                        line_empty = display;
+                        if display && i + 1 < spans.len() {
+                            ui.end_row();
+                        }
+                    }
+                    // the source, the reason on hover
+                    Err(e) => {
+                        let delimiter = if display { "$$" } else { "$" };
+                        let style = SpanStyle { code: true, ..span.style.clone() };
+                        let job = LayoutJob::single_section(
+                            format!("{delimiter}{}{delimiter}", span.text),
+                            span_format(ui, &style, base));
+                        ui.label(job).on_hover_text(e);
+                        line_empty = false;
+                    }
+                }
             }
-            .unwrap_or_else(|| FontId::proportional(14.0)); // fallback
-
-            let mut format = TextFormat {
-                font_id,
-                italics: state.is_italic || state.is_blockquote,
-                color: if state.is_blockquote {
-                    Color32::DARK_GRAY
-                } else {
-                    ui.style().visuals.text_color()
-                },
-                background: if state.is_blockquote {
-                    Color32::LIGHT_GRAY
-                } else {
-                    Color32::TRANSPARENT
-                },
-                ..Default::default()
+            self.draw_flow(ui, &spans[start..], base);
+        }).response
+    }
+
+    /// Text continuing the current line of a wrapped layout, false without spans
+    fn draw_flow(&mut self, ui: &mut egui::Ui, spans: &[Span], base: &TextFormat) -> bool {
+        if spans.is_empty() {
+            return false;
+        }
+        let (job, links) = layout_spans(ui, spans, base);
+        let (pos, galley, response) = egui::Label::new(job)
+            .sense(egui::Sense::click())
+            .wrap_mode(egui::TextWrapMode::Wrap)
+            .layout_in_ui(ui);
+        ui.painter().galley(pos, galley.clone(), base.color);
+        if let (_, Some(link)) = link_response(ui, &galley, pos, response, &links) {
+            self.open_link(&link);
+        }
+        true
+    }
+
+    fn open_link(&mut self, link: &str) {
+        if let Some(anchor) = link.strip_prefix('#') {
+            scroll_to_anchor(&self.ctx, anchor);
+        } else if let Some(name) = link.strip_prefix(NOTE_LINK_PREFIX) {
+            self.opened_note = Some(name.to_string());
+        } else {
+            self.ctx.open_url(egui::OpenUrl {
+                url: link.to_string(),
+                new_tab: true,
+            });
+            println!("Clicked on external link to: {}", link);
+        }
+    }
+
+    /// Alerts (> [!NOTE]) get a title and a colored bar, their text isn't dimmed
+    fn draw_quote(&mut self, ui: &mut egui::Ui, kind: Option<BlockQuoteKind>, blocks: &[(usize, Block)]) {
+        let alert = kind.map(alert_title);
+        let response = egui::Frame::new()
+            .fill(ui.visuals().faint_bg_color)
+            .inner_margin(egui::Margin { left: 12, right: 7, top: 7, bottom: 0 })
+            .show(ui, |ui| {
+                ui.set_width(ui.available_width());
+                if let Some((title, color)) = alert {
+                    ui.label(egui::RichText::new(title).strong().color(color));
+                }
+                self.draw_blocks(ui, blocks, alert.is_none());
+            })
+            .response;
+        // bar on the left side
+        let rect = response.rect;
+        let color = alert.map_or(ui.visuals().weak_text_color(), |(_, x)| x);
+        ui.painter().line_segment(
+            [rect.left_top(), rect.left_bottom()],
+            Stroke::new(3.0, color));
+        ui.add_space(BLOCK_SPACING);
+    }
+
+    fn draw_list(&mut self, ui: &mut egui::Ui, start: Option<u64>, items: &[ListItem], quote: bool) {
+        for (i, item) in items.iter().enumerate() {
+            ui.horizontal_top(|ui| {
+                ui.spacing_mut().item_spacing.x = 0.0;
+                match item.task {
+                    Some((checked, offset)) => {
+                        ui.allocate_ui(egui::vec2(LIST_INDENT, 0.0), |ui| {
+                            let mut checked = checked;
+                            // offsets of embedded notes aren't in this note
+                            let checkbox = egui::Checkbox::new(&mut checked, "");
+                            if ui.add_enabled(self.embed_depth == 0, checkbox).changed() {
+                                self.toggled_task = Some(offset);
+                            }
+                        });
+                    }
+                    None => {
+                        let marker = match start {
+                            Some(x) => format!("{}.", x + i as u64),
+                            None => BULLETS[self.list_depth % BULLETS.len()].to_string(),
+                        };
+                        let format = body_format(ui, quote);
+                        let (rect, _) = ui.allocate_exact_size(
+                            egui::vec2(LIST_INDENT, format.font_id.size), egui::Sense::hover());
+                        ui.painter().text(
+                            rect.right_top() - egui::vec2(6.0, 0.0),
+                            egui::Align2::RIGHT_TOP,
+                            marker,
+                            format.font_id,
+                            format.color);
+                    }
+                }
+                ui.vertical(|ui| {
+                    self.list_depth += 1;
+                    self.draw_blocks(ui, &item.blocks, quote);
+                    self.list_depth -= 1;
+                });
+            });
+        }
+    }
+
+    /// Terms in bold, their definitions indented below them
+    fn draw_definitions(&mut self, ui: &mut egui::Ui, items: &[(Vec<Span>, Vec<Blocks>)], quote: bool) {
+        for (term, definitions) in items {
+            let format = TextFormat {
+                color: ui.visuals().strong_text_color(),
+                ..body_format(ui, quote)
             };
+            self.draw_spans(ui, term, &format);
+            for blocks in definitions {
+                ui.horizontal_top(|ui| {
+                    ui.add_space(LIST_INDENT);
+                    ui.vertical(|ui| self.draw_blocks(ui, blocks, quote));
+                });
+            }
+        }
+        ui.add_space(BLOCK_SPACING);
+    }
 
-            layout_job.append(&text, 0.0, format);
-        } else if state.is_list_item {
-            if let Some(prefix) = state.list_prefix.take() {
-                let l = format!("{}{}", prefix, text);
-                ui.label(l);
+    /// Numbered definitions below a line, references scroll here
+    fn draw_footnotes(&mut self, ui: &mut egui::Ui, footnotes: &[Footnote]) {
+        // definitions are out of the source order, they would break the map
+        let source_map = std::mem::take(&mut self.source_map);
+        ui.separator();
+        for footnote in footnotes {
+            let response = ui.horizontal_top(|ui| {
+                ui.spacing_mut().item_spacing.x = 0.0;
+                let format = TextFormat {
+                    font_id: FontId::proportional(TextStyle::Small.resolve(ui.style()).size),
+                    ..body_format(ui, false)
+                };
+                let (rect, _) = ui.allocate_exact_size(
+                    egui::vec2(LIST_INDENT, format.font_id.size), egui::Sense::hover());
+                ui.painter().text(
+                    rect.right_top() - egui::vec2(6.0, 0.0),
+                    egui::Align2::RIGHT_TOP,
+                    format!("{}.", footnote.number),
+                    format.font_id,
+                    format.color);
+                ui.vertical(|ui| self.draw_blocks(ui, &footnote.blocks, false));
+            }).response;
+            if self.scroll_to.as_deref() == Some(footnote_anchor(&footnote.label).as_str()) {
+                response.scroll_to_me(Some(egui::Align::TOP));
+                self.scroll_to = None;
             }
-        } else {
-            let mut rt = RichText::new(text);
-            if state.is_bold {
-                rt = rt.strong();
-            } 
-            if state.is_italic {
-                rt = rt.italics();
-            } 
-            ui.label(rt);
         }
+        self.source_map = source_map;
+    }
+
+    /// Framed content of the other note below a link to it
+    fn draw_embed(&mut self, ui: &mut egui::Ui, embed: &Embed) {
+        let content = self.embeds.resolve(embed, self.options);
+        // blocks of other notes would break the map
+        let source_map = std::mem::take(&mut self.source_map);
+        egui::Frame::new()
+            .stroke(ui.visuals().widgets.noninteractive.bg_stroke)
+            .inner_margin(7.0)
+            .corner_radius(4.0)
+            .show(ui, |ui| {
+                ui.set_width(ui.available_width());
+                let title = egui::RichText::new(format!("📄 {}", embed.title())).small();
+                if ui.link(title).on_hover_text("Open note").clicked() {
+                    self.opened_note = Some(embed.name.clone());
+                }
+                match content {
+                    Ok(md) => {
+                        let blocks = parse_blocks(&md, self.options);
+                        self.embeds.enter(embed);
+                        self.embed_depth += 1;
+                        self.draw_blocks(ui, &blocks, false);
+                        self.embed_depth -= 1;
+                        self.embeds.leave();
+                    }
+                    Err(e) => {
+                        ui.colored_label(ui.visuals().warn_fg_color, format!("⚠ {e}"));
+                    }
+                }
+            });
+        self.source_map = source_map;
+        ui.add_space(BLOCK_SPACING);
+    }
+
+    /// Grid with the column alignment of the table, header in bold
+    fn draw_table(&mut self, ui: &mut egui::Ui, alignments: &[Alignment], rows: &[Vec<Vec<Span>>]) {
+        egui::Grid::new(("md_table", self.table_count))
+            .striped(true)
+            .spacing([12.0, 4.0])
+            .show(ui, |ui| {
+                for (r, row) in rows.iter().enumerate() {
+                    for (i, cell) in row.iter().enumerate() {
+                        let layout = match alignments.get(i) {
+                            Some(Alignment::Right) => egui::Layout::right_to_left(egui::Align::Center),
+                            Some(Alignment::Center) => egui::Layout::top_down(egui::Align::Center),
+                            _ => egui::Layout::left_to_right(egui::Align::Center),
+                        };
+                        let mut format = body_format(ui, false);
+                        if r == 0 {
+                            format.color = ui.visuals().strong_text_color();
+                        }
+                        ui.with_layout(layout, |ui| {
+                            self.draw_spans(ui, cell, &format);
+                        });
+                    }
+                    ui.end_row();
+                }
+            });
+        ui.add_space(BLOCK_SPACING);
+    }
+}
+
+fn body_format(ui: &egui::Ui, quote: bool) -> TextFormat {
+    let visuals = ui.visuals();
+    TextFormat {
+        font_id: TextStyle::Body.resolve(ui.style()),
+        color: if quote { visuals.weak_text_color() } else { visuals.text_color() },
+        italics: quote,
+        ..Default::default()
+    }
+}
+
+fn span_format(ui: &egui::Ui, style: &SpanStyle, base: &TextFormat) -> TextFormat {
+    let visuals = ui.visuals();
+    let mut format = base.clone();
+    if style.strong {
+        format.color = visuals.strong_text_color();
+    }
+    if style.emphasis {
+        format.italics = true;
+    }
+    if style.strikethrough {
+        format.strikethrough = Stroke::new(1.0, format.color);
+    }
+    if style.superscript || style.subscript {
+        format.font_id.size *= 0.7;
+        format.valign = if style.superscript { egui::Align::TOP } else { egui::Align::BOTTOM };
+    }
+    if style.code {
+        format.font_id = FontId::new(base.font_id.size * 0.9, FontFamily::Monospace);
+        format.background = visuals.code_bg_color;
+    }
+    if style.link.is_some() {
+        format.color = visuals.hyperlink_color;
+        format.underline = Stroke::new(1.0, visuals.hyperlink_color);
+    }
+    format
+}
+
+/// Title and color of an alert quote
+fn alert_title(kind: BlockQuoteKind) -> (&'static str, Color32) {
+    match kind {
+        BlockQuoteKind::Note => ("ℹ Note", Color32::from_rgb(9, 105, 218)),
+        BlockQuoteKind::Tip => ("💡 Tip", Color32::from_rgb(26, 127, 55)),
+        BlockQuoteKind::Important => ("❗ Important", Color32::from_rgb(130, 80, 223)),
+        BlockQuoteKind::Warning => ("⚠ Warning", Color32::from_rgb(154, 103, 0)),
+        BlockQuoteKind::Caution => ("⛔ Caution", Color32::from_rgb(207, 34, 46)),
+    }
+}
+
+/// Job of the spans with the links by section
+fn layout_spans(ui: &egui::Ui, spans: &[Span], base: &TextFormat) -> (LayoutJob, Vec<Option<String>>) {
+    let mut job = LayoutJob::default();
+    let mut links = Vec::new();
+    for span in spans {
+        job.append(&span.text, 0.0, span_format(ui, &span.style, base));
+        links.push(span.style.link.clone());
+    }
+    (job, links)
+}
+
+/// Label of the job, links are given by section.
+/// Also returns the link clicked in this frame.
+fn rich_label(ui: &mut egui::Ui, mut job: LayoutJob, links: &[Option<String>]) -> (egui::Response, Option<String>) {
+    job.wrap.max_width = ui.available_width();
+    let galley = ui.fonts(|f| f.layout_job(job));
+    let response = ui.add(egui::Label::new(galley.clone()).sense(egui::Sense::click()));
+    let origin = response.rect.min;
+    link_response(ui, &galley, origin, response, links)
+}
+
+/// Pointer and hover text over a link of the galley drawn at origin,
+/// with the link clicked in this frame
+fn link_response(
+    ui: &egui::Ui,
+    galley: &egui::Galley,
+    origin: egui::Pos2,
+    response: egui::Response,
+    links: &[Option<String>],
+) -> (egui::Response, Option<String>) {
+    let Some(link) = hovered_link(galley, origin, &response, links) else {
+        return (response, None);
+    };
+
+    ui.ctx().set_cursor_icon(egui::CursorIcon::PointingHand);
+    let response = response.on_hover_text(&link);
+    let clicked = response.clicked().then_some(link);
+    (response, clicked)
+}
+
+fn hovered_link(galley: &egui::Galley, origin: egui::Pos2, response: &egui::Response, links: &[Option<String>]) -> Option<String> {
+    if links.iter().all(|x| x.is_none()) {
+        return None;
+    }
+    let pos = (response.hover_pos()? - origin).to_pos2();
+    let glyph = galley.rows.iter()
+        .filter(|row| row.rect.contains(pos))
+        .flat_map(|row| row.glyphs.iter())
+        .find(|g| g.pos.x <= pos.x && pos.x < g.pos.x + g.advance_width)?;
+    links.get(glyph.section_index as usize)?.clone()
+}
+
+/// Highlighted by language, with a copy button in the corner
+fn code_block(ui: &mut egui::Ui, code: &str, lang: &str) {
+    let dark_mode = ui.visuals().dark_mode;
+    egui::Frame::new()
+        .fill(crate::code_highlight::code_background(dark_mode))
+        .inner_margin(7.0)
+        .corner_radius(4.0)
+        .show(ui, |ui| {
+            ui.set_width(ui.available_width());
+            ui.horizontal(|ui| {
+                ui.weak(lang);
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    if ui.small_button("📋").on_hover_text("Copy").clicked() {
+                        ui.ctx().copy_text(code.to_string());
+                    }
+                });
+            });
+            let job = crate::code_highlight::highlight_code(ui.ctx(), ui.style(), code, lang);
+            ui.add(egui::Label::new(job).wrap_mode(egui::TextWrapMode::Wrap));
+        });
+    ui.add_space(BLOCK_SPACING);
 }
@@ -0,0 +1,318 @@
+//! Markdown highlighting for the editor, see TextEdit::layouter.
+//! Spans come from the pulldown_cmark offset iterator, so they match the preview.
+use std::ops::Range;
+use pulldown_cmark::{Event, Options, Parser, Tag};
+use egui::text::{LayoutJob, TextFormat};
+use egui::{Color32, FontId, Stroke, TextStyle};
+use eframe::egui;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Kind {
+    Heading,
+    Emphasis,
+    Strong,
+    Strikethrough,
+    Code,
+    Link,
+    Quote,
+    Marker, // '#', '*', '`', list bullets, ...
+}
+
+const KINDS: usize = 8;
+
+impl Kind {
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Editor colours, follow the dark/light theme
+#[derive(Clone, Copy, Hash)]
+struct Palette {
+    text: Color32,
+    strong: Color32,
+    heading: Color32,
+    code: Color32,
+    code_bg: Color32,
+    link: Color32,
+    quote: Color32,
+    marker: Color32,
+}
+
+impl Palette {
+    fn new(visuals: &egui::Visuals) -> Self {
+        if visuals.dark_mode {
+            Self {
+                text: visuals.text_color(),
+                strong: visuals.strong_text_color(),
+                heading: Color32::from_rgb(0x82, 0xb4, 0xff),
+                code: Color32::from_rgb(0xe0, 0xa8, 0x70),
+                code_bg: Color32::from_gray(40),
+                link: visuals.hyperlink_color,
+                quote: Color32::from_gray(150),
+                marker: Color32::from_gray(110),
+            }
+        } else {
+            Self {
+                text: visuals.text_color(),
+                strong: visuals.strong_text_color(),
+                heading: Color32::from_rgb(0x1c, 0x4f, 0xa8),
+                code: Color32::from_rgb(0xa0, 0x4a, 0x10),
+                code_bg: Color32::from_gray(235),
+                link: visuals.hyperlink_color,
+                quote: Color32::from_gray(100),
+                marker: Color32::from_gray(150),
+            }
+        }
+    }
+}
+
+/// Highlighted text, cached until the text, the theme or the
+/// markdown extensions of the archive change
+pub fn highlight_md(ctx: &egui::Context, style: &egui::Style, text: &str, options: Options) -> LayoutJob {
+    #[derive(Default)]
+    struct Highlighter;
+
+    type Key<'a> = (&'a FontId, &'a FontId, Palette, u32, &'a str);
+
+    impl egui::cache::ComputerMut<Key<'_>, LayoutJob> for Highlighter {
+        fn compute(&mut self, (body, mono, palette, options, text): Key) -> LayoutJob {
+            layout(body, mono, &palette, Options::from_bits_truncate(options), text)
+        }
+    }
+
+    type HighlightCache = egui::cache::FrameCache<LayoutJob, Highlighter>;
+
+    let body = TextStyle::Body.resolve(style);
+    let mono = TextStyle::Monospace.resolve(style);
+    let palette = Palette::new(&style.visuals);
+    ctx.memory_mut(|mem| {
+        mem.caches
+            .cache::<HighlightCache>()
+            .get((&body, &mono, palette, options.bits(), text))
+    })
+}
+
+fn layout(body: &FontId, mono: &FontId, palette: &Palette, options: Options, text: &str) -> LayoutJob {
+    let spans = spans(text, options);
+
+    // sweep over span starts and ends, count open spans of every kind
+    let mut events: Vec<(usize, usize, i32)> = Vec::with_capacity(spans.len() * 2);
+    for (range, kind) in &spans {
+        if range.start < range.end {
+            events.push((range.start, kind.index(), 1));
+            events.push((range.end, kind.index(), -1));
+        }
+    }
+    events.sort_by_key(|(pos, _, delta)| (*pos, *delta));
+
+    let mut job = LayoutJob::default();
+    let mut open = [0i32; KINDS];
+    let mut pos = 0;
+    for (at, kind, delta) in events {
+        if at > pos {
+            job.append(&text[pos..at], 0.0, format(&open, body, mono, palette));
+            pos = at;
+        }
+        open[kind] += delta;
+    }
+    if pos < text.len() {
+        job.append(&text[pos..], 0.0, format(&open, body, mono, palette));
+    }
+    job
+}
+
+fn format(open: &[i32; KINDS], body: &FontId, mono: &FontId, palette: &Palette) -> TextFormat {
+    let is = |kind: Kind| open[kind.index()] > 0;
+
+    let mut format = TextFormat {
+        font_id: body.clone(),
+        color: palette.text,
+        ..Default::default()
+    };
+    if is(Kind::Quote) {
+        format.color = palette.quote;
+        format.italics = true;
+    }
+    if is(Kind::Heading) {
+        format.color = palette.heading;
+    }
+    if is(Kind::Emphasis) {
+        format.italics = true;
+    }
+    if is(Kind::Strong) && !is(Kind::Heading) {
+        format.color = palette.strong;
+    }
+    if is(Kind::Strikethrough) {
+        format.strikethrough = Stroke::new(1.0, format.color);
+    }
+    if is(Kind::Link) {
+        format.color = palette.link;
+        format.underline = Stroke::new(1.0, palette.link);
+    }
+    if is(Kind::Code) {
+        format.font_id = mono.clone();
+        format.color = palette.code;
+        format.background = palette.code_bg;
+        format.italics = false;
+    }
+    if is(Kind::Marker) {
+        format.color = palette.marker;
+        format.underline = Stroke::NONE;
+    }
+    format
+}
+
+/// Byte ranges of highlighted parts, may overlap.
+/// Options are the markdown extensions of the archive, same as in the preview.
+fn spans(text: &str, options: Options) -> Vec<(Range<usize>, Kind)> {
+    let mut spans = Vec::new();
+    let mut table_rows = Vec::new();
+    let mut table_cells = Vec::new();
+
+    for (event, range) in Parser::new_ext(text, options).into_offset_iter() {
+        let source = &text[range.clone()];
+        match event {
+            Event::Start(Tag::Heading { .. }) => {
+                spans.push((range.clone(), Kind::Heading));
+                let hashes = source.len() - source.trim_start_matches('#').len();
+                spans.push((range.start..range.start + hashes, Kind::Marker));
+            }
+            Event::Start(Tag::Emphasis) => {
+                spans.push((range.clone(), Kind::Emphasis));
+                push_delimiters(&mut spans, &range, source, &['*', '_'], 1);
+            }
+            Event::Start(Tag::Strong) => {
+                spans.push((range.clone(), Kind::Strong));
+                push_delimiters(&mut spans, &range, source, &['*', '_'], 2);
+            }
+            Event::Start(Tag::Strikethrough) => {
+                spans.push((range.clone(), Kind::Strikethrough));
+                push_delimiters(&mut spans, &range, source, &['~'], 2);
+            }
+            Event::Start(Tag::CodeBlock(_)) => {
+                spans.push((range.clone(), Kind::Code));
+            }
+            Event::Code(_) => {
+                spans.push((range.clone(), Kind::Code));
+                let ticks = source.len() - source.trim_start_matches('`').len();
+                push_delimiters(&mut spans, &range, source, &['`'], ticks);
+            }
+            Event::Start(Tag::Link { .. }) | Event::Start(Tag::Image { .. }) => {
+                spans.push((range.clone(), Kind::Link));
+                // "[text]" is the link, "(url)" is syntax
+                if let Some(i) = source.rfind("](") {
+                    spans.push((range.start + i..range.end, Kind::Marker));
+                }
+            }
+            Event::Start(Tag::BlockQuote(_)) => {
+                spans.push((range.clone(), Kind::Quote));
+            }
+            Event::Start(Tag::Item) => {
+                let marker = source.find(char::is_whitespace).unwrap_or(source.len());
+                spans.push((range.start..range.start + marker, Kind::Marker));
+            }
+            Event::TaskListMarker(_) => {
+                spans.push((range.clone(), Kind::Marker));
+            }
+            Event::Start(Tag::Superscript) => {
+                push_delimiters(&mut spans, &range, source, &['^'], 1);
+            }
+            Event::Start(Tag::Subscript) => {
+                push_delimiters(&mut spans, &range, source, &['~'], 1);
+            }
+            Event::InlineMath(_) | Event::DisplayMath(_) => {
+                spans.push((range.clone(), Kind::Code));
+                let dollars = source.len() - source.trim_start_matches('$').len();
+                push_delimiters(&mut spans, &range, source, &['$'], dollars);
+            }
+            Event::FootnoteReference(_) => {
+                spans.push((range.clone(), Kind::Link));
+            }
+            Event::Start(Tag::FootnoteDefinition(_)) => {
+                // "[^label]:"
+                let label = source.find("]:").map_or(0, |x| x + 2);
+                spans.push((range.start..range.start + label, Kind::Marker));
+            }
+            Event::Start(Tag::MetadataBlock(_)) => {
+                spans.push((range.clone(), Kind::Code));
+            }
+            Event::Start(Tag::DefinitionListTitle) => {
+                spans.push((range.clone(), Kind::Strong));
+            }
+            // ": definition"
+            Event::Start(Tag::DefinitionListDefinition) if source.starts_with(':') => {
+                spans.push((range.start..range.start + 1, Kind::Marker));
+            }
+            Event::Start(Tag::Table(_)) => {
+                // "| --- | :-: |" below the head
+                let head = source.find('\n').map_or(source.len(), |x| x + 1);
+                let delimiter = source[head..].find('\n').map_or(source.len(), |x| head + x);
+                spans.push((range.start + head..range.start + delimiter, Kind::Marker));
+            }
+            Event::Start(Tag::TableHead) => {
+                spans.push((range.clone(), Kind::Strong));
+                table_rows.push(range.clone());
+            }
+            Event::Start(Tag::TableRow) => table_rows.push(range.clone()),
+            Event::Start(Tag::TableCell) => table_cells.push(range.clone()),
+            _ => {}
+        }
+    }
+
+    // pipes between the cells of table rows
+    for row in &table_rows {
+        for (i, _) in text[row.clone()].match_indices('|') {
+            let pos = row.start + i;
+            if !table_cells.iter().any(|x| x.contains(&pos)) {
+                spans.push((pos..pos + 1, Kind::Marker));
+            }
+        }
+    }
+
+    let code: Vec<Range<usize>> = spans.iter()
+        .filter(|(_, kind)| *kind == Kind::Code)
+        .map(|(range, _)| range.clone())
+        .collect();
+    for range in wiki_links(text) {
+        if !code.iter().any(|x| x.start <= range.start && range.end <= x.end) {
+            spans.push((range.start..range.start + 2, Kind::Marker));
+            spans.push((range.start + 2..range.end - 2, Kind::Link));
+            spans.push((range.end - 2..range.end, Kind::Marker));
+        }
+    }
+    spans
+}
+
+/// Opening and closing delimiters of an inline element, e.g. "**" of strong
+fn push_delimiters(
+    spans: &mut Vec<(Range<usize>, Kind)>,
+    range: &Range<usize>,
+    source: &str,
+    chars: &[char],
+    len: usize) {
+    let starts = source.starts_with(chars);
+    let ends = source.ends_with(chars);
+    if starts && ends && source.len() >= len * 2 {
+        spans.push((range.start..range.start + len, Kind::Marker));
+        spans.push((range.end - len..range.end, Kind::Marker));
+    }
+}
+
+/// "[[Note name]]", on a single line
+fn wiki_links(text: &str) -> Vec<Range<usize>> {
+    let mut links = Vec::new();
+    let mut pos = 0;
+    while let Some(start) = text[pos..].find("[[").map(|x| x + pos) {
+        let rest = &text[start + 2..];
+        match rest.find("]]") {
+            Some(end) if !rest[..end].contains('\n') && end > 0 => {
+                let end = start + 2 + end + 2;
+                links.push(start..end);
+                pos = end;
+            }
+            _ => pos = start + 2,
+        }
+    }
+    links
+}
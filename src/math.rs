@@ -0,0 +1,776 @@
+//! TeX math of the preview and the html export, "$x^2$" and "$$\frac{a}{b}$$".
+//! pulldown-latex parses the TeX and writes the MathML of the export. The
+//! preview builds nodes from its events and lays them out with egui text
+//! and lines. Environments (\begin{...}) aren't laid out, such formulas
+//! are shown as TeX source.
+use std::sync::Arc;
+use egui::{Color32, FontId, Galley, Pos2, Rect, Stroke, Vec2, pos2, vec2};
+use egui::text::{LayoutJob, TextFormat};
+use eframe::egui;
+use pulldown_latex::{Parser, ParserError, RenderConfig, Storage, push_mathml};
+use pulldown_latex::config::DisplayMode;
+use pulldown_latex::event::{
+    Content, DelimiterType, Dimension, DimensionUnit, Event, Font, Grouping, ScriptPosition,
+    ScriptType, StateChange, Visual,
+};
+
+const SCRIPT_SCALE: f32 = 0.7;
+const FRACTION_SCALE: f32 = 0.85; // numerators and denominators of inline formulas
+const BIG_SCALE: f32 = 1.4; // \sum, \int, ... of display formulas
+const AXIS: f32 = 0.3; // height of fraction bars above the baseline, em
+const MAX_DEPTH: usize = 50; // of nested groups and arguments
+// in a group, outside of its inner groups. pulldown-latex parses arguments
+// without braces recursively, so "\sqrt\sqrt..." could overflow the stack
+const MAX_COMMANDS: usize = 100;
+
+/// Spacing class of an atom, as in TeX
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Class {
+    Ord,
+    Op, // \sin, \log, ...
+    Big, // \sum, \int, ...
+    Bin,
+    Rel,
+    Punct,
+    Open,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Accent {
+    Bar,
+    Hat,
+    Tilde,
+    Vec,
+    Dot,
+    Ddot,
+}
+
+#[derive(Debug)]
+enum Node {
+    Atom { text: String, class: Class, italic: bool },
+    Text(String), // \text{...}, spaces are kept
+    Row(Vec<Node>),
+    Scripts { base: Box<Node>, sup: Option<Box<Node>>, sub: Option<Box<Node>>, position: ScriptPosition },
+    Frac { num: Box<Node>, den: Box<Node>, bar: bool },
+    Sqrt { index: Option<Box<Node>>, body: Box<Node> },
+    Accent { body: Box<Node>, accent: Accent },
+    Fenced { open: String, body: Box<Node>, close: String }, // \left( ... \right)
+    Negated(Box<Node>), // \not
+    Space(f32), // em
+}
+
+impl Node {
+    /// None for spaces, they don't change the spacing of their neighbours
+    fn class(&self) -> Option<Class> {
+        match self {
+            Node::Atom { class, .. } => Some(*class),
+            Node::Scripts { base, .. } => base.class(),
+            Node::Negated(body) => body.class(),
+            Node::Space(_) => None,
+            _ => Some(Class::Ord),
+        }
+    }
+}
+
+type ParseResult<T> = Result<T, String>;
+
+/// Events of the formula, Err with the first error of the parser
+fn events<'a>(tex: &'a str, storage: &'a Storage) -> ParseResult<Vec<Event<'a>>> {
+    let (depth, commands) = nesting(tex);
+    // the parser slows down with the square of the depth
+    if depth > MAX_DEPTH {
+        return Err("nested too deep".into());
+    }
+    if commands > MAX_COMMANDS {
+        return Err("too many commands".into());
+    }
+    Parser::new(tex, storage)
+        .collect::<Result<_, _>>()
+        // the next lines show the source with a marker
+        .map_err(|e| e.to_string().lines().next().unwrap_or_default().to_string())
+}
+
+/// Deepest group and the most commands and scripts of a group,
+/// those of its inner groups aren't counted
+fn nesting(tex: &str) -> (usize, usize) {
+    let mut groups = vec![0];
+    let (mut depth, mut commands) = (0, 0);
+    let mut chars = tex.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' => groups.push(0),
+            '}' => {
+                groups.pop();
+                if groups.is_empty() {
+                    groups.push(0);
+                }
+            }
+            '\\' | '^' | '_' => {
+                // \{ and \} aren't groups
+                if c == '\\' {
+                    chars.next();
+                }
+                if let Some(count) = groups.last_mut() {
+                    *count += 1;
+                    commands = commands.max(*count);
+                }
+            }
+            _ => (),
+        }
+        depth = depth.max(groups.len() - 1);
+    }
+    (depth, commands)
+}
+
+fn parse(tex: &str) -> ParseResult<Node> {
+    let storage = Storage::new();
+    let mut builder = Builder {
+        events: events(tex, &storage)?.into_iter(),
+        depth: 0,
+        font: None,
+    };
+    Ok(Node::Row(builder.row()?))
+}
+
+/// Nodes from the events, which come before the elements they apply to:
+/// "x^2" is Script, x, 2
+struct Builder<'a> {
+    events: std::vec::IntoIter<Event<'a>>,
+    depth: usize,
+    font: Option<Font>, // \mathrm, \mathbb, ... until the end of the group
+}
+
+impl<'a> Builder<'a> {
+    /// Counts a level of nesting, every group and argument goes through here.
+    /// Leave with self.depth -= 1.
+    fn enter(&mut self) -> ParseResult<()> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err("nested too deep".into());
+        }
+        Ok(())
+    }
+
+    /// Nodes until the end of the group or the formula
+    fn row(&mut self) -> ParseResult<Vec<Node>> {
+        self.enter()?;
+        let font = self.font;
+        let mut nodes = Vec::new();
+        while let Some(event) = self.events.next() {
+            if event == Event::End {
+                break;
+            }
+            if let Some(node) = self.node(event)? {
+                nodes.push(node);
+            }
+        }
+        self.font = font;
+        self.depth -= 1;
+        Ok(nodes)
+    }
+
+    /// Next element, the base, script, numerator, ... of the one before
+    fn argument(&mut self) -> ParseResult<Node> {
+        let event = self.events.next().ok_or("missing argument")?;
+        self.enter()?;
+        let node = self.node(event);
+        self.depth -= 1;
+        Ok(node?.unwrap_or(Node::Row(Vec::new())))
+    }
+
+    /// None for state changes, they apply to the rest of the group
+    fn node(&mut self, event: Event<'a>) -> ParseResult<Option<Node>> {
+        let node = match event {
+            Event::Content(content) => self.content(content),
+            Event::Begin(Grouping::Normal) => Node::Row(self.row()?),
+            Event::Begin(Grouping::LeftRight(open, close)) => {
+                let body = Node::Row(self.row()?);
+                Node::Fenced {
+                    open: open.map(String::from).unwrap_or_default(),
+                    body: Box::new(body),
+                    close: close.map(String::from).unwrap_or_default(),
+                }
+            }
+            Event::Begin(_) | Event::EnvironmentFlow(_) => return Err("environments are not supported".into()),
+            Event::End => return Err("missing argument".into()),
+            Event::Visual(Visual::SquareRoot) => Node::Sqrt { index: None, body: Box::new(self.argument()?) },
+            Event::Visual(Visual::Root) => {
+                let body = self.argument()?;
+                Node::Sqrt { index: Some(Box::new(self.argument()?)), body: Box::new(body) }
+            }
+            // \binom has a zero thickness
+            Event::Visual(Visual::Fraction(thickness)) => Node::Frac {
+                num: Box::new(self.argument()?),
+                den: Box::new(self.argument()?),
+                bar: thickness.is_none_or(|x| x.value > 0.0),
+            },
+            Event::Visual(Visual::Negation) => Node::Negated(Box::new(self.argument()?)),
+            Event::Script { ty, position } => self.scripts(ty, position)?,
+            Event::Space { width, .. } => Node::Space(width.map_or(0.0, em)),
+            Event::StateChange(StateChange::Font(font)) => {
+                self.font = font;
+                return Ok(None);
+            }
+            // the formula is drawn in one color, the size follows the nesting
+            Event::StateChange(_) => return Ok(None),
+        };
+        Ok(Some(node))
+    }
+
+    /// The base, the subscript and the superscript, in this order.
+    /// Accents are superscripts above the base, "\hat x" is Script, x, ^
+    fn scripts(&mut self, ty: ScriptType, position: ScriptPosition) -> ParseResult<Node> {
+        let base = Box::new(self.argument()?);
+        let sub = match ty {
+            ScriptType::Superscript => None,
+            _ => Some(Box::new(self.argument()?)),
+        };
+        let sup = match ty {
+            ScriptType::Subscript => None,
+            _ => Some(Box::new(self.argument()?)),
+        };
+        let accent = match (position, sub.is_none(), sup.as_deref()) {
+            (ScriptPosition::AboveBelow, true, Some(Node::Atom { text, .. })) => accent(text),
+            _ => None,
+        };
+        Ok(match accent {
+            Some(accent) => Node::Accent { body: base, accent },
+            None => Node::Scripts { base, sup, sub, position },
+        })
+    }
+
+    fn content(&self, content: Content) -> Node {
+        let (text, class) = match content {
+            Content::Text(text) => return Node::Text(text.to_string()),
+            Content::Ordinary { content, .. } => return self.ordinary(content),
+            Content::Number(text) => (text.to_string(), Class::Ord),
+            Content::Function(text) => (text.to_string(), Class::Op),
+            Content::LargeOp { content, .. } => (content.to_string(), Class::Big),
+            Content::BinaryOp { content, .. } => (content.to_string(), Class::Bin),
+            Content::Relation { content, .. } => {
+                let mut buf = [0; 8];
+                (String::from_utf8_lossy(content.encode_utf8_to_buf(&mut buf)).into_owned(), Class::Rel)
+            }
+            Content::Delimiter { content, ty: DelimiterType::Open, .. } => (content.to_string(), Class::Open),
+            Content::Delimiter { content, .. } => (content.to_string(), Class::Ord),
+            Content::Punctuation(c) => (c.to_string(), Class::Punct),
+        };
+        Node::Atom { text, class, italic: false }
+    }
+
+    /// Latin and lowercase greek letters are italic unless the font is upright
+    fn ordinary(&self, c: char) -> Node {
+        let letter = c.is_alphabetic() && (c.is_ascii() || c.is_lowercase());
+        let italic = match self.font {
+            None | Some(Font::BoldSymbol) => letter,
+            Some(Font::Italic | Font::BoldItalic | Font::Script | Font::BoldScript
+                | Font::SansSerifItalic | Font::SansSerifBoldItalic) => true,
+            Some(_) => false,
+        };
+        let text = match self.font {
+            Some(Font::DoubleStruck) => double_struck(c),
+            _ => c,
+        };
+        Node::Atom { text: text.to_string(), class: Class::Ord, italic }
+    }
+}
+
+fn accent(mark: &str) -> Option<Accent> {
+    match mark {
+        "‾" | "¯" => Some(Accent::Bar),
+        "^" | "ˆ" => Some(Accent::Hat),
+        "~" | "˜" => Some(Accent::Tilde),
+        "→" => Some(Accent::Vec),
+        "˙" => Some(Accent::Dot),
+        "¨" => Some(Accent::Ddot),
+        _ => None,
+    }
+}
+
+/// Width of a space in em, fixed units are taken at a 10pt font
+fn em(dimension: Dimension) -> f32 {
+    let pt = match dimension.unit {
+        DimensionUnit::Em => return dimension.value,
+        DimensionUnit::Ex => return dimension.value * 0.43,
+        DimensionUnit::Mu => return dimension.value / 18.0,
+        DimensionUnit::Pt => 1.0,
+        DimensionUnit::Pc => 12.0,
+        DimensionUnit::In => 72.27,
+        DimensionUnit::Bp => 72.27 / 72.0,
+        DimensionUnit::Cm => 72.27 / 2.54,
+        DimensionUnit::Mm => 72.27 / 25.4,
+        DimensionUnit::Dd => 1238.0 / 1157.0,
+        DimensionUnit::Cc => 12.0 * 1238.0 / 1157.0,
+        DimensionUnit::Sp => 1.0 / 65536.0,
+    };
+    dimension.value * pt / 10.0
+}
+
+/// \mathbb{R} -> ℝ, letters without a common glyph stay as they are
+fn double_struck(c: char) -> char {
+    match c {
+        'C' => 'ℂ',
+        'H' => 'ℍ',
+        'N' => 'ℕ',
+        'P' => 'ℙ',
+        'Q' => 'ℚ',
+        'R' => 'ℝ',
+        'Z' => 'ℤ',
+        c => c,
+    }
+}
+
+/// Laid out part of a formula, positions are relative to the left end of its baseline
+#[derive(Default)]
+struct MathBox {
+    width: f32,
+    ascent: f32,
+    descent: f32,
+    items: Vec<Item>,
+}
+
+enum Item {
+    Text(Pos2, Arc<Galley>), // top left
+    Line(Pos2, Pos2, f32), // with the width
+    Dot(Pos2, f32), // with the radius
+}
+
+impl MathBox {
+    fn height(&self) -> f32 {
+        self.ascent + self.descent
+    }
+
+    /// Adds the other box with its baseline starting at the offset
+    fn place(&mut self, other: MathBox, offset: Vec2) {
+        self.ascent = self.ascent.max(other.ascent - offset.y);
+        self.descent = self.descent.max(other.descent + offset.y);
+        self.width = self.width.max(offset.x + other.width);
+        self.items.extend(other.items.into_iter().map(|item| match item {
+            Item::Text(pos, galley) => Item::Text(pos + offset, galley),
+            Item::Line(a, b, width) => Item::Line(a + offset, b + offset, width),
+            Item::Dot(pos, radius) => Item::Dot(pos + offset, radius),
+        }));
+    }
+
+    /// Moved to the middle of a wider box
+    fn centered(self, width: f32) -> MathBox {
+        let mut math = MathBox::default();
+        let x = (width - self.width) / 2.0;
+        math.place(self, vec2(x, 0.0));
+        math.width = width;
+        math
+    }
+
+    fn line(&mut self, from: Pos2, to: Pos2, width: f32) {
+        self.items.push(Item::Line(from, to, width));
+    }
+}
+
+struct Layouter<'a> {
+    ui: &'a egui::Ui,
+    color: Color32,
+    display: bool,
+}
+
+impl Layouter<'_> {
+    fn layout(&self, node: &Node, size: f32) -> MathBox {
+        match node {
+            Node::Atom { text, class: Class::Big, .. } if self.display => {
+                // centered on the axis
+                let big = self.text(text, size * BIG_SCALE, false);
+                let shift = -AXIS * size - (big.descent - big.ascent) / 2.0;
+                let mut math = MathBox::default();
+                math.place(big, vec2(0.0, shift));
+                math
+            }
+            Node::Atom { text, italic, .. } => self.text(text, size, *italic),
+            Node::Text(text) => self.text(text, size, false),
+            Node::Row(nodes) => self.row(nodes, size),
+            Node::Scripts { base, sup, sub, position } => {
+                // \sum and \lim have theirs on the side in inline formulas
+                let limits = match position {
+                    ScriptPosition::AboveBelow => true,
+                    ScriptPosition::Movable => self.display,
+                    ScriptPosition::Right => false,
+                };
+                self.scripts(base, sup.as_deref(), sub.as_deref(), size, limits)
+            }
+            Node::Frac { num, den, bar } => self.frac(num, den, *bar, size),
+            Node::Sqrt { index, body } => self.sqrt(index.as_deref(), body, size),
+            Node::Accent { body, accent } => self.accent(body, *accent, size),
+            Node::Fenced { open, body, close } => self.fenced(open, body, close, size),
+            Node::Negated(body) => self.negated(body, size),
+            Node::Space(em) => MathBox { width: em * size, ..Default::default() },
+        }
+    }
+
+    fn text(&self, text: &str, size: f32, italic: bool) -> MathBox {
+        let job = LayoutJob::single_section(text.to_string(), TextFormat {
+            font_id: FontId::proportional(size),
+            color: self.color,
+            italics: italic,
+            ..Default::default()
+        });
+        let galley = self.ui.fonts(|f| f.layout_job(job));
+        let ascent = galley.rows.first()
+            .and_then(|row| row.glyphs.first())
+            .map_or(size * 0.8, |glyph| glyph.pos.y);
+        let mut math = MathBox {
+            // room for the slant
+            width: galley.size().x + if italic { size * 0.05 } else { 0.0 },
+            ascent,
+            descent: galley.size().y - ascent,
+            items: Vec::new(),
+        };
+        math.items.push(Item::Text(pos2(0.0, -ascent), galley));
+        math
+    }
+
+    /// Nodes next to each other with TeX like spacing between classes
+    fn row(&self, nodes: &[Node], size: f32) -> MathBox {
+        let mut math = MathBox::default();
+        let mut prev = None;
+        for node in nodes {
+            let mut class = node.class();
+            // "-x", "(-x)", "= -x"
+            if class == Some(Class::Bin)
+                && matches!(prev, None | Some(Class::Bin | Class::Rel | Class::Punct | Class::Open)) {
+                class = Some(Class::Ord);
+            }
+            let (left, right) = match class {
+                Some(Class::Bin) => (0.22, 0.22),
+                Some(Class::Rel) => (0.28, 0.28),
+                Some(Class::Punct) => (0.0, 0.17),
+                Some(Class::Op | Class::Big) if prev.is_some() => (0.17, 0.1),
+                Some(Class::Op | Class::Big) => (0.0, 0.1),
+                _ => (0.0, 0.0),
+            };
+            let x = math.width + left * size;
+            math.place(self.layout(node, size), vec2(x, 0.0));
+            math.width += right * size;
+            if class.is_some() {
+                prev = class;
+            }
+        }
+        math
+    }
+
+    fn scripts(&self, base: &Node, sup: Option<&Node>, sub: Option<&Node>, size: f32, limits: bool) -> MathBox {
+        let base = self.layout(base, size);
+        let sup = sup.map(|x| self.layout(x, size * SCRIPT_SCALE));
+        let sub = sub.map(|x| self.layout(x, size * SCRIPT_SCALE));
+        let gap = size * 0.1;
+        let mut math = MathBox::default();
+
+        if limits {
+            // centered above and below the operator
+            let width = [Some(&base), sup.as_ref(), sub.as_ref()].into_iter()
+                .flatten()
+                .map(|x| x.width)
+                .fold(0.0, f32::max);
+            let (base_ascent, base_descent) = (base.ascent, base.descent);
+            math.place(base.centered(width), Vec2::ZERO);
+            if let Some(sup) = sup {
+                let y = -base_ascent - gap - sup.descent;
+                math.place(sup.centered(width), vec2(0.0, y));
+            }
+            if let Some(sub) = sub {
+                let y = base_descent + gap + sub.ascent;
+                math.place(sub.centered(width), vec2(0.0, y));
+            }
+            return math;
+        }
+
+        let x = base.width + size * 0.05;
+        let mut sup_shift = (base.ascent - size * 0.48).max(size * 0.45);
+        let mut sub_shift = (base.descent - size * 0.1).max(size * 0.2);
+        if let (Some(sup), Some(sub)) = (&sup, &sub) {
+            // keep them apart
+            let overlap = (sub.ascent - sub_shift) - (sup_shift - sup.descent) + gap;
+            if overlap > 0.0 {
+                sup_shift += overlap / 2.0;
+                sub_shift += overlap / 2.0;
+            }
+        }
+        math.place(base, Vec2::ZERO);
+        if let Some(sup) = sup {
+            math.place(sup, vec2(x, -sup_shift));
+        }
+        if let Some(sub) = sub {
+            math.place(sub, vec2(x, sub_shift));
+        }
+        math
+    }
+
+    fn frac(&self, num: &Node, den: &Node, bar: bool, size: f32) -> MathBox {
+        let inner = if self.display { size } else { size * FRACTION_SCALE };
+        let num = self.layout(num, inner);
+        let den = self.layout(den, inner);
+        let thickness = (size / 16.0).max(1.0);
+        let gap = size * 0.15;
+        let axis = AXIS * size;
+        let width = num.width.max(den.width) + size * 0.2;
+
+        let mut math = MathBox::default();
+        let num_y = -axis - gap - num.descent;
+        let den_y = -axis + gap + den.ascent;
+        math.place(num.centered(width), vec2(0.0, num_y));
+        math.place(den.centered(width), vec2(0.0, den_y));
+        if bar {
+            math.line(pos2(size * 0.05, -axis), pos2(width - size * 0.05, -axis), thickness);
+        }
+        math.width = width;
+        math
+    }
+
+    fn sqrt(&self, index: Option<&Node>, body: &Node, size: f32) -> MathBox {
+        let body = self.layout(body, size);
+        let thickness = (size / 16.0).max(1.0);
+        let gap = size * 0.12;
+        let top = -(body.ascent + gap);
+        let bottom = body.descent;
+        let tick = bottom - (bottom - top) * 0.45;
+
+        // room for the index left of the sign
+        let index = index.map(|x| self.layout(x, size * SCRIPT_SCALE * 0.8));
+        let left = index.as_ref().map_or(0.0, |x| (x.width - size * 0.3).max(0.0));
+
+        let mut math = MathBox::default();
+        let body_x = left + size * 0.55;
+        let end = body_x + body.width + size * 0.1;
+        math.line(pos2(left, tick), pos2(left + size * 0.12, tick - size * 0.05), thickness);
+        math.line(pos2(left + size * 0.12, tick - size * 0.05), pos2(left + size * 0.25, bottom), thickness * 1.5);
+        math.line(pos2(left + size * 0.25, bottom), pos2(left + size * 0.5, top), thickness);
+        math.line(pos2(left + size * 0.5, top), pos2(end, top), thickness);
+        math.ascent = -top + thickness;
+        math.place(body, vec2(body_x, 0.0));
+        if let Some(index) = index {
+            let x = left + size * 0.3 - index.width;
+            let y = tick - size * 0.1 - index.descent;
+            math.place(index, vec2(x, y));
+        }
+        math.width = end;
+        math
+    }
+
+    /// Accents are drawn with lines, font glyphs sit at different heights
+    fn accent(&self, body: &Node, accent: Accent, size: f32) -> MathBox {
+        let body = self.layout(body, size);
+        let thickness = (size / 16.0).max(1.0);
+        let y = -(body.ascent + size * 0.1);
+        let h = size * 0.15;
+        let x0 = size * 0.05;
+        let x1 = (body.width - size * 0.05).max(x0 + size * 0.3);
+        let mid = (x0 + x1) / 2.0;
+
+        let mut math = MathBox::default();
+        math.place(body, Vec2::ZERO);
+        match accent {
+            Accent::Bar => math.line(pos2(x0, y), pos2(x1, y), thickness),
+            Accent::Hat => {
+                math.line(pos2(mid - h, y), pos2(mid, y - h), thickness);
+                math.line(pos2(mid, y - h), pos2(mid + h, y), thickness);
+            }
+            Accent::Tilde => {
+                let w = (x1 - x0) / 3.0;
+                math.line(pos2(x0, y), pos2(x0 + w, y - h), thickness);
+                math.line(pos2(x0 + w, y - h), pos2(x0 + 2.0 * w, y), thickness);
+                math.line(pos2(x0 + 2.0 * w, y), pos2(x1, y - h), thickness);
+            }
+            Accent::Vec => {
+                math.line(pos2(x0, y), pos2(x1, y), thickness);
+                math.line(pos2(x1 - h, y - h * 0.7), pos2(x1, y), thickness);
+                math.line(pos2(x1 - h, y + h * 0.7), pos2(x1, y), thickness);
+            }
+            Accent::Dot => math.items.push(Item::Dot(pos2(mid, y), thickness * 1.2)),
+            Accent::Ddot => {
+                math.items.push(Item::Dot(pos2(mid - h, y), thickness * 1.2));
+                math.items.push(Item::Dot(pos2(mid + h, y), thickness * 1.2));
+            }
+        }
+        math.ascent = math.ascent.max(-y + h + thickness);
+        math
+    }
+
+    /// Struck through from the bottom left to the top right
+    fn negated(&self, body: &Node, size: f32) -> MathBox {
+        let body = self.layout(body, size);
+        let thickness = (size / 16.0).max(1.0);
+        let (ascent, descent) = (body.ascent, body.descent);
+        let mid = body.width / 2.0;
+        let mut math = MathBox::default();
+        math.place(body, Vec2::ZERO);
+        math.line(pos2(mid - size * 0.15, descent), pos2(mid + size * 0.15, -ascent), thickness);
+        math
+    }
+
+    /// Delimiters grow with the body, centered on the axis
+    fn fenced(&self, open: &str, body: &Node, close: &str, size: f32) -> MathBox {
+        let body = self.layout(body, size);
+        let axis = AXIS * size;
+        let height = 2.0 * (body.ascent - axis).max(body.descent + axis);
+        let delimiter = |text: &str| {
+            let normal = self.text(text, size, false);
+            let scale = (height / normal.height()).max(1.0);
+            let delimiter = if scale > 1.05 { self.text(text, size * scale, false) } else { normal };
+            let shift = -axis - (delimiter.descent - delimiter.ascent) / 2.0;
+            (delimiter, shift)
+        };
+
+        let mut math = MathBox::default();
+        if !open.is_empty() {
+            let (open, shift) = delimiter(open);
+            math.place(open, vec2(0.0, shift));
+        }
+        let x = math.width;
+        math.place(body, vec2(x, 0.0));
+        if !close.is_empty() {
+            let (close, shift) = delimiter(close);
+            let x = math.width;
+            math.place(close, vec2(x, shift));
+        }
+        math
+    }
+}
+
+/// Formula of the preview, laid out once per frame
+pub struct Formula {
+    math: MathBox,
+    axis: f32,
+    color: Color32,
+}
+
+impl Formula {
+    /// Err with the reason when the TeX isn't supported
+    pub fn new(ui: &egui::Ui, tex: &str, size: f32, color: Color32, display: bool) -> Result<Self, String> {
+        let node = parse(tex)?;
+        let layouter = Layouter { ui, color, display };
+        Ok(Formula {
+            math: layouter.layout(&node, size),
+            axis: AXIS * size,
+            color,
+        })
+    }
+
+    /// The height is the same above and below the axis,
+    /// so a formula centered in a line of text lines up with it
+    pub fn size(&self) -> Vec2 {
+        let half = (self.math.ascent - self.axis).max(self.math.descent + self.axis);
+        vec2(self.math.width, 2.0 * half)
+    }
+
+    /// Draws the formula in the middle of the rect
+    pub fn paint(&self, painter: &egui::Painter, rect: Rect) {
+        let origin = pos2(rect.center().x - self.math.width / 2.0, rect.center().y + self.axis);
+        for item in &self.math.items {
+            match item {
+                Item::Text(pos, galley) => {
+                    painter.galley(origin + pos.to_vec2(), galley.clone(), self.color);
+                }
+                Item::Line(a, b, width) => {
+                    painter.line_segment([origin + a.to_vec2(), origin + b.to_vec2()], Stroke::new(*width, self.color));
+                }
+                Item::Dot(pos, radius) => {
+                    painter.circle_filled(origin + pos.to_vec2(), *radius, self.color);
+                }
+            }
+        }
+    }
+}
+
+/// MathML of the formula for the html export. When the TeX isn't
+/// supported it's kept for a client side renderer like KaTeX.
+pub fn to_mathml(tex: &str, display: bool) -> String {
+    let storage = Storage::new();
+    let config = RenderConfig {
+        display_mode: if display { DisplayMode::Block } else { DisplayMode::Inline },
+        ..Default::default()
+    };
+    let mut html = String::new();
+    let res = events(tex, &storage).map(|events| {
+        push_mathml(&mut html, events.into_iter().map(Ok::<_, ParserError>), config)
+    });
+    match res {
+        Ok(Ok(())) => html,
+        _ => format!("<span class=\"math {}\">{}</span>",
+            if display { "math-display" } else { "math-inline" },
+            escape(tex)),
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unbraced_chains_stop_at_max_commands() {
+        for (start, chain) in [("", "\\sqrt "), ("", "\\frac "), ("", "\\hat "), ("x^", "\\sqrt "), ("", "\\frac x")] {
+            let tex = format!("{start}{}x", chain.repeat(100_000));
+            assert_eq!(parse(&tex).err().as_deref(), Some("too many commands"), "{start}{chain}");
+        }
+        // the commands of inner groups count for these
+        assert_eq!(nesting("\\sqrt{\\sqrt{\\sqrt{x}}}"), (3, 1));
+        assert_eq!(nesting("\\{ a^2_1 \\} {\\alpha}"), (1, 4));
+    }
+
+    #[test]
+    fn deep_groups_stop_at_max_depth() {
+        let braces = format!("{}x{}", "{".repeat(100_000), "}".repeat(100_000));
+        assert_eq!(parse(&braces).err().as_deref(), Some("nested too deep"));
+        // \sqrt and its group are two levels of nodes
+        let tex = format!("{}x{}", "\\sqrt{".repeat(MAX_DEPTH / 2 - 1), "}".repeat(MAX_DEPTH / 2 - 1));
+        assert!(parse(&tex).is_ok());
+    }
+
+    #[test]
+    fn too_many_commands_fall_back_to_source_in_mathml() {
+        let tex = format!("{}x", "\\sqrt ".repeat(1000));
+        assert!(to_mathml(&tex, false).starts_with("<span class=\"math math-inline\">"));
+        assert_eq!(to_mathml("\\foo <b>", true), "<span class=\"math math-display\">\\foo &lt;b&gt;</span>");
+    }
+
+    #[test]
+    fn mathml_of_formulas() {
+        let cases = [
+            ("x^2", "<msup><mi>x</mi><mn>2</mn></msup>"),
+            ("\\frac12", "<mfrac><mn>1</mn><mn>2</mn></mfrac>"),
+            ("\\sqrt[3]{x}", "<mroot><mrow><mi>x</mi></mrow><mn>3</mn></mroot>"),
+            ("\\text{if }", "<mtext>if&nbsp;</mtext>"),
+            ("\\begin{pmatrix}1&2\\\\3&4\\end{pmatrix}", "<mtr><mtd><mn>1</mn></mtd><mtd><mn>2</mn></mtd></mtr>"),
+        ];
+        for (tex, expected) in cases {
+            let out = to_mathml(tex, false);
+            assert!(out.starts_with("<math display=\"inline\">") && out.contains(expected), "{tex}: {out}");
+        }
+        assert!(to_mathml("\\sum_{i=1}^n i", true).contains("<munderover>"));
+    }
+
+    #[test]
+    fn nodes_of_formulas() {
+        let Node::Row(nodes) = parse("\\hat x \\sum_i \\not= \\mathbb{R}").unwrap() else {
+            panic!("not a row");
+        };
+        assert!(matches!(&nodes[0], Node::Accent { accent: Accent::Hat, .. }));
+        assert!(matches!(&nodes[1],
+            Node::Scripts { position: ScriptPosition::Movable, sup: None, sub: Some(_), .. }));
+        assert_eq!(nodes[1].class(), Some(Class::Big));
+        assert_eq!(nodes[2].class(), Some(Class::Rel));
+        let Node::Row(inner) = &nodes[3] else {
+            panic!("not a group");
+        };
+        assert!(matches!(&inner[0], Node::Atom { text, italic: false, .. } if text == "ℝ"));
+    }
+
+    #[test]
+    fn errors_of_malformed_tex() {
+        assert!(parse("{x").unwrap_err().contains("unbalanced group"));
+        assert!(parse("x}").unwrap_err().contains("unbalanced group"));
+        assert!(parse("x^2^3").unwrap_err().contains("superscript twice"));
+        assert!(parse("\\foo").unwrap_err().contains("unknown primitive"));
+        assert_eq!(parse("\\begin{matrix}1\\end{matrix}").err().as_deref(), Some("environments are not supported"));
+    }
+}
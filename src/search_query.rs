@@ -0,0 +1,462 @@
+//! Search query language
+//!
+//! rust cargo          - both words (full text)
+//! "exact phrase"      - phrase
+//! rust*               - prefix
+//! -word               - exclude, works with filters too: -tag:done
+//! name:text           - name contains text
+//! tag:todo            - note has #todo
+//! parent:projects     - parent name contains text
+//! created:>2025-01-01 - also <, >=, <= and created:2025-01-01 for the day
+//! updated:<7d         - updated within the last 7 days, units: d, w, m, y
+//! in:trash            - search in the trash instead of notes
+//!
+//! Query is compiled to an FTS5 MATCH expression + SQL predicates on note n.
+use rusqlite::types::Value;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Cmp {
+    Before,
+    BeforeOrOn,
+    On,
+    AfterOrOn,
+    After,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DateValue {
+    Day(String), // YYYY-MM-DD
+    Ago(i64, &'static str), // amount, SQLite modifier unit: days, months, years
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Term {
+    Text { text: String, phrase: bool, prefix: bool },
+    Name(String),
+    Tag(String),
+    Parent(String),
+    Created(Cmp, DateValue),
+    Updated(Cmp, DateValue),
+    InTrash,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Clause {
+    pub term: Term,
+    pub negated: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct ParseError {
+    pub message: String,
+    pub position: usize, // char position in the query, starts at 1
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} (at position {})", self.message, self.position)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Compiled query, ready for Database::search
+#[derive(Debug, Default)]
+pub struct SearchQuery {
+    pub fts: Option<String>, // MATCH expression, None - no full text terms
+    pub predicates: Vec<String>, // SQL on note n, joined with AND
+    pub params: Vec<Value>, // parameters of fts + predicates, in order
+    pub tags: Vec<(String, bool)>, // (tag, negated), checked on the content
+    pub in_trash: bool,
+}
+
+pub fn parse(input: &str) -> Result<Vec<Clause>, ParseError> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut clauses = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        if chars[i].is_whitespace() {
+            i += 1;
+            continue;
+        }
+        let start = i;
+
+        let negated = chars[i] == '-';
+        if negated {
+            i += 1;
+            if i >= chars.len() || chars[i].is_whitespace() {
+                return Err(error("Nothing to exclude after '-'", start));
+            }
+        }
+
+        // "phrase"
+        if chars[i] == '"' {
+            let (text, end) = read_quoted(&chars, i)?;
+            i = end;
+            let prefix = chars.get(i) == Some(&'*');
+            if prefix {
+                i += 1;
+            }
+            if text.trim().is_empty() {
+                return Err(error("Empty phrase", start));
+            }
+            clauses.push(Clause {
+                term: Term::Text { text, phrase: true, prefix },
+                negated,
+            });
+            continue;
+        }
+
+        // word or field:value
+        let word_start = i;
+        while i < chars.len() && !chars[i].is_whitespace() && chars[i] != ':' && chars[i] != '"' {
+            i += 1;
+        }
+        let word: String = chars[word_start..i].iter().collect();
+
+        if chars.get(i) == Some(&':') {
+            i += 1;
+            let value_start = i;
+            let value = if chars.get(i) == Some(&'"') {
+                let (text, end) = read_quoted(&chars, i)?;
+                i = end;
+                text
+            } else {
+                while i < chars.len() && !chars[i].is_whitespace() {
+                    i += 1;
+                }
+                chars[value_start..i].iter().collect()
+            };
+            if value.trim().is_empty() {
+                return Err(error(&format!("Missing value after '{}:'", word), value_start));
+            }
+            let term = parse_field(&word, &value, word_start, value_start)?;
+            if term == Term::InTrash && negated {
+                return Err(error("'in:trash' cannot be excluded", start));
+            }
+            clauses.push(Clause { term, negated });
+            continue;
+        }
+
+        if chars.get(i) == Some(&'"') {
+            return Err(error("Quote must start a phrase, add a space before it", i));
+        }
+
+        let prefix = word.ends_with('*');
+        let text = word.trim_end_matches('*').to_string();
+        if text.is_empty() {
+            return Err(error("'*' must follow a word", word_start));
+        }
+        clauses.push(Clause {
+            term: Term::Text { text, phrase: false, prefix },
+            negated,
+        });
+    }
+
+    if clauses.is_empty() {
+        return Err(error("Empty query", 0));
+    }
+    Ok(clauses)
+}
+
+fn error(message: &str, position: usize) -> ParseError {
+    ParseError {
+        message: message.to_string(),
+        position: position + 1,
+    }
+}
+
+/// Text between quotes starting at chars[start], returns the text
+/// and the position after the closing quote
+fn read_quoted(chars: &[char], start: usize) -> Result<(String, usize), ParseError> {
+    let mut i = start + 1;
+    while i < chars.len() && chars[i] != '"' {
+        i += 1;
+    }
+    if i >= chars.len() {
+        return Err(error("Missing closing quote", start));
+    }
+    Ok((chars[start + 1..i].iter().collect(), i + 1))
+}
+
+fn parse_field(field: &str, value: &str, field_pos: usize, value_pos: usize) -> Result<Term, ParseError> {
+    match field.to_lowercase().as_str() {
+        "name" => Ok(Term::Name(value.to_string())),
+        "tag" => Ok(Term::Tag(value.trim_start_matches('#').to_lowercase())),
+        "parent" => Ok(Term::Parent(value.to_string())),
+        "created" => {
+            let (cmp, date) = parse_date(value, value_pos)?;
+            Ok(Term::Created(cmp, date))
+        }
+        "updated" => {
+            let (cmp, date) = parse_date(value, value_pos)?;
+            Ok(Term::Updated(cmp, date))
+        }
+        "in" => match value.to_lowercase().as_str() {
+            "trash" => Ok(Term::InTrash),
+            _ => Err(error(&format!("Unknown value 'in:{}', only 'in:trash' is supported", value), value_pos)),
+        },
+        _ => Err(error(
+            &format!("Unknown filter '{}:', use name:, tag:, parent:, created:, updated: or in:", field),
+            field_pos)),
+    }
+}
+
+fn parse_date(value: &str, pos: usize) -> Result<(Cmp, DateValue), ParseError> {
+    let (cmp, rest) = if let Some(x) = value.strip_prefix(">=") {
+        (Cmp::AfterOrOn, x)
+    } else if let Some(x) = value.strip_prefix("<=") {
+        (Cmp::BeforeOrOn, x)
+    } else if let Some(x) = value.strip_prefix('>') {
+        (Cmp::After, x)
+    } else if let Some(x) = value.strip_prefix('<') {
+        (Cmp::Before, x)
+    } else if let Some(x) = value.strip_prefix('=') {
+        (Cmp::On, x)
+    } else {
+        (Cmp::On, value)
+    };
+
+    if chrono::NaiveDate::parse_from_str(rest, "%Y-%m-%d").is_ok() {
+        return Ok((cmp, DateValue::Day(rest.to_string())));
+    }
+
+    // relative: 7d, 2w, 3m, 1y
+    let unit_pos = rest.char_indices().last().map(|(i, _)| i).unwrap_or(0);
+    let amount = rest[..unit_pos].parse::<u32>().ok().map(i64::from);
+    let unit = &rest[unit_pos..];
+    match (amount, unit) {
+        (Some(n), "d") => Ok((cmp, DateValue::Ago(n, "days"))),
+        (Some(n), "w") => Ok((cmp, DateValue::Ago(n * 7, "days"))),
+        (Some(n), "m") => Ok((cmp, DateValue::Ago(n, "months"))),
+        (Some(n), "y") => Ok((cmp, DateValue::Ago(n, "years"))),
+        _ => Err(error(
+            &format!("Invalid date '{}', use YYYY-MM-DD or a relative value like 7d, 2w, 3m, 1y", rest),
+            pos)),
+    }
+}
+
+/// Quotes text for FTS5, so special characters are never part of the syntax
+fn fts_string(text: &str) -> String {
+    format!("\"{}\"", text.replace('"', "\"\""))
+}
+
+fn fts_term(text: &str, prefix: bool) -> String {
+    let mut x = fts_string(text);
+    if prefix {
+        x.push('*');
+    }
+    x
+}
+
+/// LIKE pattern for "contains", used with ESCAPE '\'
+fn like_contains(text: &str) -> String {
+    let escaped = text
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    format!("%{}%", escaped)
+}
+
+/// SQL for a date filter on column, pushes its parameters
+fn date_predicate(column: &str, cmp: &Cmp, date: &DateValue, params: &mut Vec<Value>) -> String {
+    match date {
+        DateValue::Day(day) => {
+            let start = "date(?)";
+            let end = "date(?, '+1 day')";
+            let (sql, n) = match cmp {
+                Cmp::Before => (format!("{column} < {start}"), 1),
+                Cmp::BeforeOrOn => (format!("{column} < {end}"), 1),
+                Cmp::AfterOrOn => (format!("{column} >= {start}"), 1),
+                Cmp::After => (format!("{column} >= {end}"), 1),
+                Cmp::On => (format!("({column} >= {start} AND {column} < {end})"), 2),
+            };
+            for _ in 0..n {
+                params.push(Value::Text(day.clone()));
+            }
+            sql
+        }
+        DateValue::Ago(amount, unit) => {
+            params.push(Value::Text(format!("-{} {}", amount, unit)));
+            let moment = "datetime('now', ?)";
+            // updated:<7d - less than 7 days ago, so after that moment
+            match cmp {
+                Cmp::Before | Cmp::BeforeOrOn => format!("{column} >= {moment}"),
+                Cmp::After | Cmp::AfterOrOn => format!("{column} < {moment}"),
+                Cmp::On => format!("date({column}) = date({moment})"),
+            }
+        }
+    }
+}
+
+pub fn compile(clauses: &[Clause]) -> SearchQuery {
+    let mut query = SearchQuery::default();
+
+    // MATCH parameter comes first in the SQL
+    let fts: Vec<String> = clauses.iter()
+        .filter(|x| !x.negated)
+        .filter_map(|x| match &x.term {
+            Term::Text { text, prefix, .. } => Some(fts_term(text, *prefix)),
+            _ => None,
+        })
+        .collect();
+    if !fts.is_empty() {
+        let x = fts.join(" AND ");
+        query.params.push(Value::Text(x.clone()));
+        query.fts = Some(x);
+    }
+
+    for clause in clauses {
+        let not = if clause.negated { "NOT " } else { "" };
+        match &clause.term {
+            Term::Text { text, prefix, .. } => {
+                if clause.negated {
+                    query.predicates.push(
+                        "n.id NOT IN (SELECT rowid FROM note_fts WHERE note_fts MATCH ?)".to_string());
+                    query.params.push(Value::Text(fts_term(text, *prefix)));
+                }
+            }
+            Term::Name(text) => {
+                query.predicates.push(format!("n.name {not}LIKE ? ESCAPE '\\'"));
+                query.params.push(Value::Text(like_contains(text)));
+            }
+            Term::Parent(text) => {
+                query.predicates.push(format!("{not}EXISTS (
+                    SELECT 1 FROM note_link l JOIN note p ON p.id = l.source_note_id
+                    WHERE l.target_note_id = n.id AND l.link_type = 'parent'
+                    AND p.name LIKE ? ESCAPE '\\')"));
+                query.params.push(Value::Text(like_contains(text)));
+            }
+            Term::Tag(tag) => {
+                // narrow down in SQL, exact check is done on the content
+                if !clause.negated {
+                    query.predicates.push("n.content LIKE ? ESCAPE '\\'".to_string());
+                    query.params.push(Value::Text(like_contains(&format!("#{tag}"))));
+                }
+                query.tags.push((tag.clone(), clause.negated));
+            }
+            Term::Created(cmp, date) => {
+                let x = date_predicate("n.created_at", cmp, date, &mut query.params);
+                query.predicates.push(format!("{not}{x}"));
+            }
+            Term::Updated(cmp, date) => {
+                let x = date_predicate("n.updated_at", cmp, date, &mut query.params);
+                query.predicates.push(format!("{not}{x}"));
+            }
+            Term::InTrash => query.in_trash = true,
+        }
+    }
+    query
+}
+
+/// parse + compile
+pub fn build(input: &str) -> Result<SearchQuery, ParseError> {
+    Ok(compile(&parse(input)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(text: &str, phrase: bool, prefix: bool) -> Term {
+        Term::Text { text: text.to_string(), phrase, prefix }
+    }
+
+    fn error_message(input: &str) -> String {
+        parse(input).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn exclusions() {
+        let clauses = parse("rust -cargo -tag:done").unwrap();
+        assert_eq!(clauses, vec![
+            Clause { term: text("rust", false, false), negated: false },
+            Clause { term: text("cargo", false, false), negated: true },
+            Clause { term: Term::Tag("done".to_string()), negated: true },
+        ]);
+
+        let query = compile(&clauses);
+        assert_eq!(query.fts.as_deref(), Some("\"rust\""));
+        assert_eq!(query.predicates,
+            vec!["n.id NOT IN (SELECT rowid FROM note_fts WHERE note_fts MATCH ?)"]);
+        assert_eq!(query.params,
+            vec![Value::Text("\"rust\"".to_string()), Value::Text("\"cargo\"".to_string())]);
+        assert_eq!(query.tags, vec![("done".to_string(), true)]);
+    }
+
+    #[test]
+    fn quoted_phrases() {
+        let clauses = parse("\"exact phrase\" \"pre\"* name:\"two words\"").unwrap();
+        assert_eq!(clauses[0].term, text("exact phrase", true, false));
+        assert_eq!(clauses[1].term, text("pre", true, true));
+        assert_eq!(clauses[2].term, Term::Name("two words".to_string()));
+
+        // operators inside a phrase are never FTS syntax
+        let query = build("\"a OR b\" c*").unwrap();
+        assert_eq!(query.fts.as_deref(), Some("\"a OR b\" AND \"c\"*"));
+    }
+
+    #[test]
+    fn tags() {
+        let clauses = parse("tag:Todo tag:#project/nisabo").unwrap();
+        assert_eq!(clauses[0].term, Term::Tag("todo".to_string()));
+        assert_eq!(clauses[1].term, Term::Tag("project/nisabo".to_string()));
+
+        let query = compile(&clauses);
+        assert_eq!(query.fts, None);
+        assert_eq!(query.params, vec![
+            Value::Text("%#todo%".to_string()),
+            Value::Text("%#project/nisabo%".to_string()),
+        ]);
+        assert_eq!(query.tags,
+            vec![("todo".to_string(), false), ("project/nisabo".to_string(), false)]);
+    }
+
+    #[test]
+    fn dates() {
+        let clauses = parse("created:>2025-01-01 updated:<7d created:2w updated:>=3m").unwrap();
+        assert_eq!(clauses[0].term, Term::Created(Cmp::After, DateValue::Day("2025-01-01".to_string())));
+        assert_eq!(clauses[1].term, Term::Updated(Cmp::Before, DateValue::Ago(7, "days")));
+        assert_eq!(clauses[2].term, Term::Created(Cmp::On, DateValue::Ago(14, "days")));
+        assert_eq!(clauses[3].term, Term::Updated(Cmp::AfterOrOn, DateValue::Ago(3, "months")));
+
+        let query = build("created:>2025-01-01 updated:<7d").unwrap();
+        assert_eq!(query.predicates, vec![
+            "n.created_at >= date(?, '+1 day')",
+            "n.updated_at >= datetime('now', ?)",
+        ]);
+        assert_eq!(query.params, vec![
+            Value::Text("2025-01-01".to_string()),
+            Value::Text("-7 days".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn invalid_dates() {
+        assert_eq!(error_message("created:>2025-13-01"),
+            "Invalid date '2025-13-01', use YYYY-MM-DD or a relative value like 7d, 2w, 3m, 1y (at position 9)");
+        assert_eq!(error_message("rust updated:<7x"),
+            "Invalid date '7x', use YYYY-MM-DD or a relative value like 7d, 2w, 3m, 1y (at position 14)");
+        assert_eq!(error_message("updated:<"),
+            "Invalid date '', use YYYY-MM-DD or a relative value like 7d, 2w, 3m, 1y (at position 9)");
+    }
+
+    #[test]
+    fn unbalanced_quotes() {
+        assert_eq!(error_message("rust \"exact phrase"), "Missing closing quote (at position 6)");
+        assert_eq!(error_message("name:\"two words"), "Missing closing quote (at position 6)");
+        assert_eq!(error_message("rust\"y\""), "Quote must start a phrase, add a space before it (at position 5)");
+    }
+
+    #[test]
+    fn other_errors() {
+        assert_eq!(error_message("  "), "Empty query (at position 1)");
+        assert_eq!(error_message("rust - cargo"), "Nothing to exclude after '-' (at position 6)");
+        assert_eq!(error_message("\"\""), "Empty phrase (at position 1)");
+        assert_eq!(error_message("tag:"), "Missing value after 'tag:' (at position 5)");
+        assert_eq!(error_message("-in:trash"), "'in:trash' cannot be excluded (at position 1)");
+        assert_eq!(error_message("size:1"),
+            "Unknown filter 'size:', use name:, tag:, parent:, created:, updated: or in: (at position 1)");
+    }
+}
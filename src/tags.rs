@@ -0,0 +1,67 @@
+//! Tags are #hashtags inside the note content, e.g. #todo or #project/nisabo
+//! "# Heading" is not a tag, there must be no space after '#'
+
+fn is_tag_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '-' || c == '/'
+}
+
+/// Tags of the content in lowercase, without '#', each tag once
+pub fn extract_tags(content: &str) -> Vec<String> {
+    let mut tags: Vec<String> = Vec::new();
+    let mut prev: Option<char> = None;
+    for (i, c) in content.char_indices() {
+        // '#' must start a word: "a#b" and "##" are not tags
+        let starts_word = prev.is_none_or(|p| p.is_whitespace() || p == '(' || p == ',');
+        if c == '#' && starts_word {
+            let rest = &content[i + 1..];
+            let len: usize = rest.chars()
+                .take_while(|x| is_tag_char(*x))
+                .map(|x| x.len_utf8())
+                .sum();
+            let tag = rest[..len].trim_end_matches(['-', '/']).to_lowercase();
+            // pure numbers are issue references like #12, not tags
+            if !tag.is_empty() && !tag.chars().all(|x| x.is_ascii_digit())
+                && !tags.contains(&tag) {
+                tags.push(tag);
+            }
+        }
+        prev = Some(c);
+    }
+    tags
+}
+
+pub fn has_tag(content: &str, tag: &str) -> bool {
+    let tag = tag.trim_start_matches('#').to_lowercase();
+    extract_tags(content).contains(&tag)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extracts_tags() {
+        assert_eq!(extract_tags("#todo and #Project/Nisabo, (#x_y) #a-b"),
+            vec!["todo", "project/nisabo", "x_y", "a-b"]);
+        assert_eq!(extract_tags("#todo #TODO #todo."), vec!["todo"]);
+        assert_eq!(extract_tags("#päivä #日本"), vec!["päivä", "日本"]);
+    }
+
+    #[test]
+    fn skips_non_tags() {
+        assert!(extract_tags("# Heading\n## Sub").is_empty());
+        assert!(extract_tags("a#b issue #12 ##x").is_empty());
+        // trailing separators are not part of the tag
+        assert_eq!(extract_tags("#done- #path/"), vec!["done", "path"]);
+    }
+
+    #[test]
+    fn has_tags() {
+        let content = "Notes #Todo #project/nisabo";
+        assert!(has_tag(content, "todo"));
+        assert!(has_tag(content, "#TODO"));
+        assert!(has_tag(content, "project/nisabo"));
+        assert!(!has_tag(content, "project"));
+        assert!(!has_tag(content, "done"));
+    }
+}
@@ -0,0 +1,68 @@
+//! Task items "- [ ] text" / "- [x] text" inside notes.
+//! They are indexed in the task table whenever a note is saved,
+//! "due:2025-06-01" in the text of an item sets its due date.
+use chrono::NaiveDate;
+use pulldown_cmark::{Event, Options, Parser};
+
+const DUE_PREFIX: &str = "due:";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TaskItem {
+    pub position: usize, // byte offset of "[ ]" in the content, see markdown::toggle_task
+    pub text: String, // without the due date
+    pub done: bool,
+    pub due: Option<String>, // YYYY-MM-DD
+    pub tags: Vec<String>, // of the item, see tags.rs
+}
+
+/// Task items of the content, in the order they appear
+pub fn extract_tasks(content: &str) -> Vec<TaskItem> {
+    let mut tasks = Vec::new();
+    for (event, range) in Parser::new_ext(content, Options::ENABLE_TASKLISTS).into_offset_iter() {
+        if let Event::TaskListMarker(done) = event {
+            // rest of the line after "[ ]"
+            let line = content[range.end..].lines().next().unwrap_or_default();
+            let (text, due) = split_due(line);
+            tasks.push(TaskItem {
+                position: range.start,
+                tags: crate::tags::extract_tags(&text),
+                text,
+                done,
+                due,
+            });
+        }
+    }
+    tasks
+}
+
+/// Tags of the content outside of the task items, they apply to all items
+pub fn note_tags(content: &str, tasks: &[TaskItem]) -> Vec<String> {
+    let mut pos = 0;
+    let mut rest = String::new();
+    for line in content.split_inclusive('\n') {
+        let range = pos..pos + line.len();
+        if !tasks.iter().any(|x| range.contains(&x.position)) {
+            rest.push_str(line);
+        }
+        pos = range.end;
+    }
+    crate::tags::extract_tags(&rest)
+}
+
+/// "call Bob due:2025-06-01" -> ("call Bob", Some("2025-06-01")),
+/// an invalid date stays in the text
+fn split_due(line: &str) -> (String, Option<String>) {
+    let mut due = None;
+    let words: Vec<&str> = line.split_whitespace()
+        .filter(|word| {
+            if due.is_none()
+                && let Some(x) = word.strip_prefix(DUE_PREFIX)
+                && NaiveDate::parse_from_str(x, "%Y-%m-%d").is_ok() {
+                due = Some(x.to_string());
+                return false;
+            }
+            true
+        })
+        .collect();
+    (words.join(" "), due)
+}
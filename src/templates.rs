@@ -0,0 +1,97 @@
+//! Templates of new notes. Variables in double braces are filled in when
+//! the note is added: "{{date}}", "{{time}}", "{{title}}", "{{parent}}",
+//! "{{date:%d.%m.%Y}}" with a chrono format, and "{{?Severity}}" for a field
+//! the add note window asks for. Unknown variables are left as they are.
+use std::collections::HashMap;
+use std::fmt::Write;
+use chrono::NaiveDateTime;
+
+const DATE_FORMAT: &str = "%Y-%m-%d";
+const TIME_FORMAT: &str = "%H:%M";
+const FIELD_PREFIX: char = '?';
+
+/// Values of the variables of a note being added
+pub struct TemplateVars<'a> {
+    pub title: &'a str,
+    pub parent: &'a str, // empty for top level notes
+    pub now: NaiveDateTime, // local time
+    pub fields: &'a HashMap<String, String>, // by field name
+}
+
+/// Names of the "{{?Field}}" variables, in order of their first use
+pub fn fields(template: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for (_, var) in variables(template) {
+        if let Some(name) = field_name(var)
+            && !names.iter().any(|x| x == name) {
+            names.push(name.to_string());
+        }
+    }
+    names
+}
+
+/// Template with the variables replaced by their values
+pub fn fill(template: &str, vars: &TemplateVars) -> String {
+    let mut out = String::new();
+    let mut pos = 0;
+    for (range, var) in variables(template) {
+        out.push_str(&template[pos..range.start]);
+        match value(var, vars) {
+            Some(x) => out.push_str(&x),
+            None => out.push_str(&template[range.clone()]),
+        }
+        pos = range.end;
+    }
+    out.push_str(&template[pos..]);
+    out
+}
+
+fn value(var: &str, vars: &TemplateVars) -> Option<String> {
+    if let Some(name) = field_name(var) {
+        return Some(vars.fields.get(name).cloned().unwrap_or_default());
+    }
+    let (name, format) = match var.split_once(':') {
+        Some((name, format)) => (name.trim(), Some(format)),
+        None => (var, None),
+    };
+    match name {
+        "title" => Some(vars.title.to_string()),
+        "parent" => Some(vars.parent.to_string()),
+        "date" => format_now(vars.now, format.unwrap_or(DATE_FORMAT)),
+        "time" => format_now(vars.now, format.unwrap_or(TIME_FORMAT)),
+        _ => None,
+    }
+}
+
+/// None for an invalid format, formatting with to_string would panic
+fn format_now(now: NaiveDateTime, format: &str) -> Option<String> {
+    let mut out = String::new();
+    write!(out, "{}", now.format(format)).ok()?;
+    Some(out)
+}
+
+fn field_name(var: &str) -> Option<&str> {
+    var.strip_prefix(FIELD_PREFIX)
+        .map(str::trim)
+        .filter(|x| !x.is_empty())
+}
+
+/// Byte ranges of "{{...}}" on a single line with their trimmed inside
+fn variables(template: &str) -> Vec<(std::ops::Range<usize>, &str)> {
+    let mut vars = Vec::new();
+    let mut pos = 0;
+    while let Some(start) = template[pos..].find("{{").map(|x| x + pos) {
+        let inner_start = start + 2;
+        let Some(end) = template[inner_start..].find("}}").map(|x| x + inner_start) else {
+            break;
+        };
+        let inner = &template[inner_start..end];
+        if inner.contains(['\n', '{']) {
+            pos = start + 1;
+            continue;
+        }
+        vars.push((start..end + 2, inner.trim()));
+        pos = end + 2;
+    }
+    vars
+}
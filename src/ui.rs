@@ -6,9 +6,18 @@ pub mod statusbar;
 pub mod toggle_compact;
 pub mod settings;
 pub mod modal_add_new_note;
+pub mod modal_templates;
 pub mod modal_rename_note;
 pub mod modal_search;
-pub mod custom_button;
+pub mod saved_searches;
+pub mod modal_tasks;
+pub mod modal_quick_open;
+pub mod modal_command_palette;
 pub mod notes;
+pub mod outline;
+pub mod calendar;
 pub mod trash;
 pub mod modal_history;
+pub mod modal_encrypt_note;
+pub mod modal_backups;
+pub mod modal_maintenance;
@@ -0,0 +1,228 @@
+use std::collections::HashMap;
+use eframe::egui::{self, RichText};
+use chrono::{Datelike, Local, Months, NaiveDate};
+use log::{error};
+use crate::app::{App};
+use crate::db::models::{LinkType, NoteDays};
+use crate::journal::{entries, entry_name, parent_note};
+use crate::templates::{TemplateVars, fill};
+
+const WEEKDAYS: [&str; 7] = ["Mo", "Tu", "We", "Th", "Fr", "Sa", "Su"];
+
+pub fn first_of_month(date: NaiveDate) -> NaiveDate {
+    date.with_day(1).unwrap_or(date)
+}
+
+impl App {
+    /// Days of a month, journal entries and days with changed notes stand out,
+    /// a click lists the notes of the day
+    pub fn show_calendar(&mut self, ctx: &egui::Context) {
+        if !self.state_calendar_load {
+            let res = self.try_load_calendar();
+            if let Err(e) = res {
+                error!("Error loading calendar: {e}");
+                self.calendar_notes.clear();
+            }
+            self.state_calendar_load = true;
+        }
+
+        egui::SidePanel::right("calendar panel")
+            .resizable(false)
+            .default_width(220.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.strong("Calendar");
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        if ui.small_button("✖").on_hover_text("Hide calendar").clicked() {
+                            self.state_calendar_open = false;
+                        }
+                    });
+                });
+                ui.separator();
+
+                self.show_month(ui);
+                ui.separator();
+
+                if let Some(day) = self.calendar_day {
+                    egui::ScrollArea::vertical()
+                        .auto_shrink([false; 2])
+                        .show(ui, |ui| self.show_day(ui, day));
+                }
+            });
+    }
+
+    fn show_month(&mut self, ui: &mut egui::Ui) {
+        let today = Local::now().date_naive();
+        let mut month = self.calendar_month;
+        ui.horizontal(|ui| {
+            if ui.small_button("◀").on_hover_text("Previous month").clicked() {
+                month = month - Months::new(1);
+            }
+            ui.label(RichText::new(month.format("%B %Y").to_string()).strong());
+            if ui.small_button("▶").on_hover_text("Next month").clicked() {
+                month = month + Months::new(1);
+            }
+            if ui.small_button("Today").clicked() {
+                month = first_of_month(today);
+                self.calendar_day = Some(today);
+            }
+        });
+        if month != self.calendar_month {
+            self.calendar_month = month;
+            self.state_calendar_load = false;
+        }
+
+        let journal = entries(&self.names, &self.journal.parent);
+        let mut changed: HashMap<NaiveDate, usize> = HashMap::new();
+        for note in &self.calendar_notes {
+            for day in note_days(note) {
+                *changed.entry(day).or_default() += 1;
+            }
+        }
+
+        egui::Grid::new("calendar grid")
+            .num_columns(7)
+            .spacing([2.0, 2.0])
+            .show(ui, |ui| {
+                for x in WEEKDAYS {
+                    ui.weak(x);
+                }
+                ui.end_row();
+
+                for _ in 0..month.weekday().num_days_from_monday() {
+                    ui.label("");
+                }
+                let days = month.iter_days().take_while(|x| x.month() == month.month());
+                for day in days {
+                    let mut text = RichText::new(day.day().to_string());
+                    let mut hover = Vec::new();
+                    if journal.contains_key(&day) {
+                        text = text.strong().color(ui.visuals().hyperlink_color);
+                        hover.push("Journal entry".to_string());
+                    }
+                    if let Some(n) = changed.get(&day) {
+                        text = text.underline();
+                        hover.push(format!("Notes created or updated: {n}"));
+                    }
+                    if day == today {
+                        text = text.italics();
+                    }
+                    let mut response = ui.selectable_label(self.calendar_day == Some(day), text);
+                    if !hover.is_empty() {
+                        response = response.on_hover_text(hover.join("\n"));
+                    }
+                    if response.clicked() {
+                        self.calendar_day = Some(day);
+                    }
+                    if day.weekday().num_days_from_monday() == 6 {
+                        ui.end_row();
+                    }
+                }
+            });
+    }
+
+    /// Journal entry and the notes created or updated on the day
+    fn show_day(&mut self, ui: &mut egui::Ui, day: NaiveDate) {
+        ui.strong(day.format("%A, %-d %B %Y").to_string());
+
+        let mut open = None;
+        match entries(&self.names, &self.journal.parent).get(&day) {
+            Some(id) => {
+                if ui.link("📔 Journal entry").clicked() {
+                    open = Some(*id);
+                }
+            }
+            None => {
+                if ui.button("📔 Write journal entry").clicked() {
+                    let res = self.try_open_journal_day(day);
+                    self.status_error = crate::utils::result(res, "Error opening journal entry");
+                }
+            }
+        }
+
+        let date = day.format("%Y-%m-%d").to_string();
+        let created: Vec<&NoteDays> = self.calendar_notes.iter()
+            .filter(|x| x.created == date)
+            .collect();
+        let updated: Vec<&NoteDays> = self.calendar_notes.iter()
+            .filter(|x| x.updated == date && x.created != date)
+            .collect();
+        for (title, notes) in [("Created", created), ("Updated", updated)] {
+            if notes.is_empty() {
+                continue;
+            }
+            ui.add_space(4.0);
+            ui.label(RichText::new(title).small().weak());
+            for note in notes {
+                if ui.selectable_label(self.selected_index == Some(note.id), &note.name).clicked() {
+                    open = Some(note.id);
+                }
+            }
+        }
+
+        if let Some(id) = open {
+            self.open_search_result(id, false);
+        }
+    }
+
+    fn try_load_calendar(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let from = self.calendar_month;
+        let to = from + Months::new(1) - chrono::Days::new(1);
+        let db = crate::db::database::Database::new(&self.db_path)?;
+        self.calendar_notes = db.get_note_days(
+            &from.format("%Y-%m-%d").to_string(),
+            &to.format("%Y-%m-%d").to_string())?;
+        Ok(())
+    }
+
+    /// Opens the journal entry of the day, a missing one is made from the
+    /// journal template, the parent note too
+    pub fn try_open_journal_day(&mut self, day: NaiveDate) -> Result<(), Box<dyn std::error::Error>> {
+        let name = entry_name(day);
+        let parent = parent_note(&self.names, &self.journal.parent);
+        let existing = parent.and_then(|x| x.children.iter().find(|child| child.name.trim() == name));
+        if let Some(id) = existing.map(|x| x.id) {
+            self.open_search_result(id, false);
+            self.calendar_day = Some(day);
+            return Ok(());
+        }
+
+        let mut db = crate::db::database::Database::new(&self.db_path)?;
+        let content = match self.journal.template.as_str() {
+            "" => None,
+            template => {
+                let templates = db.get_templates()?;
+                let template = templates.iter()
+                    .find(|x| x.name == template)
+                    .ok_or(format!("No template named \"{template}\""))?;
+                Some(fill(&template.content, &TemplateVars {
+                    title: &name,
+                    parent: self.journal.parent.trim(),
+                    now: day.and_time(Local::now().time()),
+                    fields: &HashMap::new(),
+                }))
+            }
+        };
+        let parent_id = match parent {
+            Some(x) => x.id,
+            None => db.add_new_note(self.journal.parent.trim(), None)?,
+        };
+        let id = db.add_new_note(&name, content.as_deref())?;
+        db.add_note_link(parent_id, id, LinkType::Parent)?;
+
+        // refresh ui
+        self.load_rows = false;
+        self.open_search_result(id, false);
+        self.calendar_day = Some(day);
+        Ok(())
+    }
+}
+
+/// Days the note was created and updated on, once each
+fn note_days(note: &NoteDays) -> Vec<NaiveDate> {
+    let mut days: Vec<NaiveDate> = [&note.created, &note.updated].iter()
+        .filter_map(|x| NaiveDate::parse_from_str(x, "%Y-%m-%d").ok())
+        .collect();
+    days.dedup();
+    days
+}
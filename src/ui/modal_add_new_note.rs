@@ -1,11 +1,14 @@
 use eframe::egui::{self};
 use log::{info, error};
 use crate::app::{App};
+use crate::actions::find_note;
 use crate::db::models::{LinkType};
+use crate::templates::{TemplateVars, fields, fill};
 
 impl App {
-    pub fn show_add_new_note(&mut self, ctx: &egui::Context) { 
+    pub fn show_add_new_note(&mut self, ctx: &egui::Context) {
         if self.state_add_new_note {
+            self.load_templates();
             // tmp var
             let mut open = self.state_add_new_note;
             egui::Window::new("Add new note")
@@ -27,7 +30,7 @@ impl App {
                             let add_btn = ui.add(egui::Button::new("Add"))
                                 .clicked();
 
-                            let enter_pressed = response.lost_focus() 
+                            let enter_pressed = response.lost_focus()
                                 && ui.input(|i| i.key_pressed(egui::Key::Enter));
 
                             if add_btn || enter_pressed {
@@ -36,37 +39,89 @@ impl App {
                                 }
                             }
                         }
-                  
+
                         if ui.button("Cancel").clicked() {
                             info!("Cancel clicked");
-                            self.parent_note_id = None;
-                            self.state_add_new_note = false;
-                            self.add_new_note_input.clear();
-                            self.add_new_note_error = None;
+                            self.close_add_new_note();
                         }
                     });
+
+                    if !self.templates.is_empty() {
+                        self.show_template_choice(ui);
+                    }
                 });
             if !open {
-                self.parent_note_id = None;
-                self.state_add_new_note = false;
-                self.add_new_note_input.clear();
-                self.add_new_note_error = None;
+                self.close_add_new_note();
             }
         }
     }
-    
-    fn try_add_new_note(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        let mut db = crate::db::database::Database::new(&self.db_path)?;
-        let target_id = db.add_new_note(&self.add_new_note_input)?;
 
-        if let Some(pid) = self.parent_note_id {
-            let _ = db.add_note_link(pid, target_id, LinkType::Parent);
+    /// Template of the new note and the values of its fields
+    fn show_template_choice(&mut self, ui: &mut egui::Ui) {
+        ui.separator();
+        let selected = self.add_new_note_template
+            .and_then(|id| self.templates.iter().find(|x| x.id == id));
+        let text = selected.map_or("None", |x| x.name.as_str());
+        let field_names = selected.map(|x| fields(&x.content)).unwrap_or_default();
+
+        ui.horizontal(|ui| {
+            ui.label("Template:");
+            egui::ComboBox::from_id_salt("new note template")
+                .selected_text(text)
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut self.add_new_note_template, None, "None");
+                    for template in &self.templates {
+                        ui.selectable_value(&mut self.add_new_note_template, Some(template.id), &template.name);
+                    }
+                });
+        });
+
+        if field_names.is_empty() {
+            return;
         }
+        egui::Grid::new("new note fields")
+            .num_columns(2)
+            .show(ui, |ui| {
+                for name in &field_names {
+                    ui.label(format!("{name}:"));
+                    let value = self.add_new_note_fields.entry(name.clone()).or_default();
+                    ui.text_edit_singleline(value);
+                    ui.end_row();
+                }
+            });
+    }
 
+    fn close_add_new_note(&mut self) {
         self.parent_note_id = None;
         self.state_add_new_note = false;
         self.add_new_note_input.clear();
         self.add_new_note_error = None;
+        self.add_new_note_fields.clear();
+    }
+
+    fn try_add_new_note(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let content = self.add_new_note_template
+            .and_then(|id| self.templates.iter().find(|x| x.id == id))
+            .map(|template| {
+                let parent = self.parent_note_id
+                    .and_then(|x| find_note(&self.names, x))
+                    .map_or("", |x| x.name.as_str());
+                fill(&template.content, &TemplateVars {
+                    title: self.add_new_note_input.trim(),
+                    parent,
+                    now: chrono::Local::now().naive_local(),
+                    fields: &self.add_new_note_fields,
+                })
+            });
+
+        let mut db = crate::db::database::Database::new(&self.db_path)?;
+        let target_id = db.add_new_note(&self.add_new_note_input, content.as_deref())?;
+
+        if let Some(pid) = self.parent_note_id {
+            let _ = db.add_note_link(pid, target_id, LinkType::Parent);
+        }
+
+        self.close_add_new_note();
         // refresh ui
         self.load_rows = false;
         Ok(())
@@ -0,0 +1,142 @@
+use eframe::egui::{self};
+use log::{error};
+use crate::app::{App};
+use crate::constants::RESULT_SUCCESS;
+
+impl App {
+    pub fn show_backups(&mut self, ctx: &egui::Context) {
+        if !self.state_backups_load {
+            self.backups = match self.config.backup_dir() {
+                Some(dir) => crate::backup::list_backups(&self.db_path, &dir),
+                None => Vec::new(),
+            };
+            // opening every backup takes a while, the counts come in later
+            let paths = self.backups.iter().map(|b| b.path.clone()).collect();
+            self.backup_count_rx = Some(crate::backup::count_notes_in_background(paths));
+            self.state_backups_load = true;
+        }
+        self.receive_backup_counts(ctx);
+
+        let mut open = self.state_backups_open;
+        egui::Window::new("Restore from backup")
+            .open(&mut open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                if let Some(dir) = self.config.backup_dir() {
+                    ui.label(format!("Directory: {}", dir.display()));
+                }
+                ui.horizontal(|ui| {
+                    if ui.button("Backup now").clicked() {
+                        self.backup_now();
+                    }
+                    if ui.button("Refresh").clicked() {
+                        self.state_backups_load = false;
+                    }
+                });
+                ui.separator();
+
+                if let Some(target) = self.restore_backup_target.clone() {
+                    ui.label(format!("Restore archive from {}?", target.display()));
+                    ui.label("Current state of the archive is backed up first.");
+                    ui.horizontal(|ui| {
+                        if ui.button("Restore").clicked() {
+                            if let Err(e) = self.try_restore_backup(&target) {
+                                error!("Restore failed: {e}");
+                            }
+                            self.restore_backup_target = None;
+                        }
+                        if ui.button("Cancel").clicked() {
+                            self.restore_backup_target = None;
+                        }
+                    });
+                    return;
+                }
+
+                if self.backups.is_empty() {
+                    ui.label("No backups found");
+                    return;
+                }
+
+                egui::ScrollArea::vertical()
+                    .max_height(300.0)
+                    .show(ui, |ui| {
+                        egui::Grid::new("backups_grid")
+                            .striped(true)
+                            .show(ui, |ui| {
+                                ui.strong("Created");
+                                ui.strong("Notes");
+                                ui.strong("Size");
+                                ui.end_row();
+
+                                for b in &self.backups {
+                                    ui.label(b.created_at.format("%Y-%m-%d %H:%M:%S").to_string());
+                                    ui.label(match b.note_count {
+                                        Some(x) => x.to_string(),
+                                        None if self.backup_count_rx.is_some() => "…".to_string(),
+                                        None => "?".to_string(),
+                                    });
+                                    ui.label(format!("{:.1} KB", b.size as f64 / 1024.0));
+                                    if ui.button("Restore").clicked() {
+                                        self.restore_backup_target = Some(b.path.clone());
+                                    }
+                                    ui.end_row();
+                                }
+                            });
+                    });
+            });
+        if !open {
+            self.state_backups_open = false;
+            self.restore_backup_target = None;
+            self.backup_count_rx = None;
+        }
+    }
+
+    /// Takes the note counts that came in, drops the receiver once all are there
+    fn receive_backup_counts(&mut self, ctx: &egui::Context) {
+        let Some(rx) = &self.backup_count_rx else {
+            return;
+        };
+        loop {
+            match rx.try_recv() {
+                Ok((path, count)) => {
+                    if let Some(b) = self.backups.iter_mut().find(|b| b.path == path) {
+                        b.note_count = count;
+                    }
+                }
+                Err(std::sync::mpsc::TryRecvError::Empty) => {
+                    ctx.request_repaint();
+                    return;
+                }
+                Err(std::sync::mpsc::TryRecvError::Disconnected) => {
+                    self.backup_count_rx = None;
+                    return;
+                }
+            }
+        }
+    }
+
+    fn try_restore_backup(&mut self, backup: &std::path::Path) -> Result<(), Box<dyn std::error::Error>> {
+        // save pending changes, they end up in the safety backup
+        let _ = self.try_auto_update_note_content();
+
+        let dir = self.config.backup_dir().ok_or("No backup directory")?;
+        self.status_error = crate::utils::result(
+            crate::backup::restore_backup(&self.db_path, backup, &dir),
+            "Error restoring backup");
+
+        if self.status_error == RESULT_SUCCESS {
+            self.reload_archive();
+        }
+
+        // get rid of ghost data
+        self.selected_index = None;
+        self.edited_note_id = None;
+        self.original_content = String::new();
+        self.edited_content = String::new();
+        // refresh ui
+        self.load_rows = false;
+        self.state_trash_load = false;
+        self.state_backups_load = false;
+        Ok(())
+    }
+}
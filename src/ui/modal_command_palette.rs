@@ -0,0 +1,127 @@
+use eframe::egui::{self, Key, Modifiers, TextEdit};
+use fuzzy_matcher::FuzzyMatcher;
+use fuzzy_matcher::skim::SkimMatcherV2;
+use crate::actions::Action;
+use crate::app::{App};
+
+impl App {
+    /// Ctrl+Shift+P, find an action by name and run it
+    pub fn show_command_palette(&mut self, ctx: &egui::Context) {
+        let mut open = self.state_command_palette;
+        let mut run = None;
+        egui::Window::new("Command palette")
+            .open(&mut open)
+            .title_bar(false)
+            .resizable(false)
+            .collapsible(false)
+            .anchor(egui::Align2::CENTER_TOP, [0.0, 60.0])
+            .show(ctx, |ui| {
+                ui.set_width(450.0);
+
+                // handled before the input, so it doesn't move the cursor
+                let (down, up, enter, escape) = ui.input_mut(|i| (
+                    i.consume_key(Modifiers::NONE, Key::ArrowDown),
+                    i.consume_key(Modifiers::NONE, Key::ArrowUp),
+                    i.consume_key(Modifiers::NONE, Key::Enter),
+                    i.consume_key(Modifiers::NONE, Key::Escape),
+                ));
+
+                let response = ui.add(
+                    TextEdit::singleline(&mut self.command_palette_input)
+                        .hint_text("Type a command")
+                        .desired_width(f32::INFINITY));
+                if self.command_palette_has_focus {
+                    response.request_focus();
+                    self.command_palette_has_focus = false;
+                }
+                if response.changed() {
+                    self.command_palette_selected = 0;
+                }
+
+                let actions = self.palette_actions();
+                let count = actions.len();
+                if down && count > 0 {
+                    self.command_palette_selected = (self.command_palette_selected + 1) % count;
+                }
+                if up && count > 0 {
+                    self.command_palette_selected = (self.command_palette_selected + count - 1) % count;
+                }
+                if escape {
+                    self.state_command_palette = false;
+                    return;
+                }
+                if enter {
+                    run = actions.get(self.command_palette_selected).copied();
+                    return;
+                }
+
+                if count == 0 {
+                    ui.weak("No matching commands");
+                    return;
+                }
+                ui.separator();
+
+                egui::ScrollArea::vertical()
+                    .max_height(300.0)
+                    .show(ui, |ui| {
+                        for (i, action) in actions.iter().enumerate() {
+                            let selected = i == self.command_palette_selected;
+                            let response = ui.horizontal(|ui| {
+                                let response = ui.add(egui::SelectableLabel::new(
+                                    selected, palette_title(*action)));
+                                if let Some(s) = self.keymap.shortcut(*action) {
+                                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                                        ui.weak(ctx.format_shortcut(&s));
+                                    });
+                                }
+                                response
+                            }).inner;
+                            if selected && (up || down) {
+                                response.scroll_to_me(None);
+                            }
+                            if response.clicked() {
+                                run = Some(*action);
+                            }
+                        }
+                    });
+            });
+
+        if !open || run.is_some() || !self.state_command_palette {
+            self.state_command_palette = false;
+            self.command_palette_input.clear();
+            self.command_palette_selected = 0;
+        }
+        // after closing, the action may open another window
+        if let Some(action) = run {
+            self.run_action(action);
+        }
+    }
+
+    pub fn open_command_palette(&mut self) {
+        self.state_command_palette = true;
+        self.command_palette_has_focus = true;
+    }
+
+    /// Enabled actions matching the input, best first
+    fn palette_actions(&self) -> Vec<Action> {
+        let pattern = self.command_palette_input.trim();
+        let matcher = SkimMatcherV2::default();
+        let mut ranked: Vec<(i64, Action)> = Action::ALL.iter()
+            .filter(|x| **x != Action::CommandPalette && self.is_action_enabled(**x))
+            .filter_map(|x| {
+                if pattern.is_empty() {
+                    return Some((0, *x));
+                }
+                matcher.fuzzy_match(&palette_title(*x), pattern).map(|s| (s, *x))
+            })
+            .collect();
+        // stable, equal scores keep the menu order
+        ranked.sort_by_key(|(s, _)| std::cmp::Reverse(*s));
+        ranked.into_iter().map(|(_, x)| x).collect()
+    }
+}
+
+/// "Note: Rename", the menu tells apart actions with similar names
+fn palette_title(action: Action) -> String {
+    format!("{}: {}", action.menu().0.title(), action.title())
+}
@@ -0,0 +1,198 @@
+use eframe::egui::{self};
+use log::{info, error};
+use crate::app::{App};
+use crate::constants::RESULT_SUCCESS;
+
+impl App {
+    /// Set a passphrase for a note
+    pub fn show_encrypt_note(&mut self, ctx: &egui::Context) {
+        if self.state_encrypt_note {
+            // tmp var
+            let mut open = self.state_encrypt_note;
+            egui::Window::new("Encrypt Note")
+                .open(&mut open)
+                .resizable(false)
+                .show(ctx, |ui| {
+                    ui.label("Passphrase is kept only until the app is closed.");
+                    ui.label("There is no way to recover a forgotten passphrase.");
+                    let invalid = if self.encrypt_input.is_empty() {
+                        Some("Passphrase cannot be empty")
+                    } else if self.encrypt_input != self.encrypt_confirm_input {
+                        Some("Passphrases do not match")
+                    } else {
+                        None
+                    };
+                    // a failed encryption is shown until the passphrase changes
+                    if let Some(e) = self.encrypt_error.as_deref().or(invalid) {
+                        ui.label(egui::RichText::new(e).color(egui::Color32::RED));
+                    }
+
+                    let passphrase = ui.add(egui::TextEdit::singleline(&mut self.encrypt_input)
+                        .password(true)
+                        .hint_text("Passphrase"));
+                    let response = ui.add(
+                        egui::TextEdit::singleline(&mut self.encrypt_confirm_input)
+                            .password(true)
+                            .hint_text("Confirm passphrase"));
+                    if passphrase.changed() || response.changed() {
+                        self.encrypt_error = None;
+                    }
+
+                    ui.horizontal(|ui| {
+                        if invalid.is_none() {
+                            let encrypt_btn = ui.add(egui::Button::new("Encrypt"))
+                                .clicked();
+
+                            let enter_pressed = response.lost_focus()
+                                && ui.input(|i| i.key_pressed(egui::Key::Enter));
+
+                            if (encrypt_btn || enter_pressed)
+                                && let Err(e) = self.try_encrypt_note() {
+                                error!("Encrypt failed: {e}");
+                                self.encrypt_error = Some(e.to_string());
+                            }
+                        }
+
+                        if ui.button("Cancel").clicked() {
+                            info!("Cancel clicked");
+                            self.close_encrypt_note();
+                        }
+                    });
+                });
+            if !open {
+                self.close_encrypt_note();
+            }
+        }
+    }
+
+    /// Shown instead of the editor while the selected note is locked
+    pub fn show_locked_note(&mut self, ui: &mut egui::Ui) {
+        ui.vertical_centered(|ui| {
+            ui.add_space(20.0);
+            ui.heading("🔒 This note is locked");
+            ui.add_space(10.0);
+            if let Some(e) = &self.unlock_error {
+                ui.label(egui::RichText::new(e).color(egui::Color32::RED));
+            }
+
+            let response = ui.add(
+                egui::TextEdit::singleline(&mut self.unlock_input)
+                    .password(true)
+                    .hint_text("Passphrase"));
+
+            let unlock_btn = ui.add_enabled(
+                !self.unlock_input.is_empty(),
+                egui::Button::new("Unlock")).clicked();
+
+            let enter_pressed = response.lost_focus()
+                && ui.input(|i| i.key_pressed(egui::Key::Enter));
+
+            if (unlock_btn || enter_pressed) && !self.unlock_input.is_empty()
+                && let Err(e) = self.try_unlock_note() {
+                error!("Unlock failed: {e}");
+                self.unlock_error = Some(e.to_string());
+            }
+        });
+    }
+
+    fn close_encrypt_note(&mut self) {
+        self.state_encrypt_note = false;
+        self.encrypt_note_id = None;
+        self.encrypt_input.clear();
+        self.encrypt_confirm_input.clear();
+        self.encrypt_error = None;
+    }
+
+    fn try_encrypt_note(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let id = match self.encrypt_note_id {
+            Some(x) => x,
+            None => return Ok(()),
+        };
+        // save pending changes first, they are encrypted below
+        if self.edited_note_id == Some(id) {
+            self.try_update_note_content()?;
+        }
+
+        let mut db = crate::db::database::Database::new(&self.db_path)?;
+        let note = db.get_note(id)?;
+        let key = crate::crypto::new_key(&self.encrypt_input)?;
+
+        let content = crate::crypto::encrypt(&key, &note.content.unwrap_or_default())?;
+        let mut diffs = Vec::new();
+        for x in db.select_note_diff_all(id)? {
+            diffs.push((x.id, crate::crypto::encrypt(&key, &x.diff)?));
+        }
+
+        self.status_error = match db.replace_note_content_and_diffs(id, &content, &diffs) {
+            Ok(()) => {
+                self.note_keys.insert(id, key);
+                String::from(RESULT_SUCCESS)
+            }
+            Err(e) => format!("Error encrypting note: {:?}", e),
+        };
+
+        self.close_encrypt_note();
+        // refresh ui
+        self.load_rows = false;
+        Ok(())
+    }
+
+    fn try_unlock_note(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let id = match self.locked_note_id {
+            Some(x) => x,
+            None => return Ok(()),
+        };
+        let db = crate::db::database::Database::new(&self.db_path)?;
+        let note = db.get_note(id)?;
+        let key = crate::crypto::key_for(
+            &self.unlock_input,
+            &note.content.unwrap_or_default())?;
+
+        self.note_keys.insert(id, key);
+        self.unlock_input.clear();
+        self.unlock_error = None;
+        self.try_get_note(id)
+    }
+
+    pub fn try_lock_note(&mut self, id: i64) -> Result<(), Box<dyn std::error::Error>> {
+        if self.edited_note_id == Some(id) {
+            self.try_update_note_content()?;
+        }
+        self.note_keys.remove(&id);
+
+        if self.selected_index == Some(id) {
+            self.try_get_note(id)?;
+        }
+        Ok(())
+    }
+
+    pub fn try_remove_encryption(&mut self, id: i64) -> Result<(), Box<dyn std::error::Error>> {
+        let key = match self.note_keys.get(&id) {
+            Some(x) => x.clone(),
+            None => return Ok(()),
+        };
+        if self.edited_note_id == Some(id) {
+            self.try_update_note_content()?;
+        }
+
+        let mut db = crate::db::database::Database::new(&self.db_path)?;
+        let note = db.get_note(id)?;
+        let content = crate::crypto::decrypt(&key, &note.content.unwrap_or_default())?;
+        let mut diffs = Vec::new();
+        for x in db.select_note_diff_all(id)? {
+            diffs.push((x.id, crate::crypto::decrypt(&key, &x.diff)?));
+        }
+
+        self.status_error = match db.replace_note_content_and_diffs(id, &content, &diffs) {
+            Ok(()) => {
+                self.note_keys.remove(&id);
+                String::from(RESULT_SUCCESS)
+            }
+            Err(e) => format!("Error removing encryption: {:?}", e),
+        };
+
+        // refresh ui
+        self.load_rows = false;
+        Ok(())
+    }
+}
@@ -0,0 +1,164 @@
+use eframe::egui::{self};
+use crate::app::{App};
+use crate::db::database::Database;
+
+impl App {
+    pub fn show_maintenance(&mut self, ctx: &egui::Context) {
+        if self.archive_stats.is_none() {
+            self.load_archive_stats();
+        }
+
+        let mut open = self.state_maintenance_open;
+        egui::Window::new("Maintenance")
+            .open(&mut open)
+            .resizable(false)
+            .default_width(400.0)
+            .show(ctx, |ui| {
+                if let Some(stats) = &self.archive_stats {
+                    egui::Grid::new("archive_stats_grid")
+                        .num_columns(2)
+                        .show(ui, |ui| {
+                            ui.label("Notes:");
+                            ui.label(stats.note_count.to_string());
+                            ui.end_row();
+
+                            ui.label("Trash:");
+                            ui.label(stats.trash_count.to_string());
+                            ui.end_row();
+
+                            ui.label("History:");
+                            ui.label(format!("{} versions, {}",
+                                stats.history_count,
+                                format_size(stats.history_size)));
+                            ui.end_row();
+
+                            ui.label("Archive size:");
+                            ui.label(format_size(stats.db_size));
+                            ui.end_row();
+                        });
+                }
+
+                ui.separator();
+
+                ui.horizontal_wrapped(|ui| {
+                    if ui.button("Check integrity").clicked() {
+                        self.run_maintenance(check_integrity);
+                    }
+                    if ui.button("Rebuild search index").clicked() {
+                        self.run_maintenance(rebuild_fts);
+                    }
+                    if ui.button("Rebuild task index").clicked() {
+                        self.run_maintenance(rebuild_tasks);
+                    }
+                    if ui.button("Remove orphans").clicked() {
+                        self.run_maintenance(remove_orphans);
+                    }
+                    if ui.button("Vacuum").clicked() {
+                        self.run_maintenance(vacuum);
+                    }
+                    if ui.button("Run all").clicked() {
+                        self.run_maintenance(|db, log| {
+                            check_integrity(db, log);
+                            remove_orphans(db, log);
+                            rebuild_fts(db, log);
+                            rebuild_tasks(db, log);
+                            vacuum(db, log);
+                        });
+                    }
+                });
+
+                if !self.maintenance_log.is_empty() {
+                    ui.separator();
+                    egui::ScrollArea::vertical()
+                        .max_height(200.0)
+                        .show(ui, |ui| {
+                            for line in &self.maintenance_log {
+                                ui.label(line);
+                            }
+                        });
+                }
+            });
+        if !open {
+            self.state_maintenance_open = false;
+            self.maintenance_log.clear();
+            self.archive_stats = None;
+        }
+    }
+
+    fn run_maintenance<F>(&mut self, f: F)
+        where F: FnOnce(&mut Database, &mut Vec<String>) {
+        self.maintenance_log.clear();
+        match Database::new(&self.db_path) {
+            Ok(mut db) => f(&mut db, &mut self.maintenance_log),
+            Err(e) => self.maintenance_log.push(format!("Failed to open archive: {e}")),
+        }
+        self.load_archive_stats();
+        // refresh ui
+        self.load_rows = false;
+        self.state_trash_load = false;
+        self.state_tasks_load = false;
+    }
+
+    fn load_archive_stats(&mut self) {
+        self.archive_stats = Database::new(&self.db_path)
+            .and_then(|db| db.stats())
+            .ok();
+    }
+}
+
+fn check_integrity(db: &mut Database, log: &mut Vec<String>) {
+    match db.integrity_check() {
+        Ok(rows) => {
+            for row in rows {
+                log.push(format!("Integrity: {row}"));
+            }
+        }
+        Err(e) => log.push(format!("Integrity check failed: {e}")),
+    }
+    match db.foreign_key_check() {
+        Ok(rows) if rows.is_empty() => log.push("Foreign keys: ok".to_string()),
+        Ok(rows) => {
+            for row in rows {
+                log.push(format!("Foreign keys: {row}"));
+            }
+        }
+        Err(e) => log.push(format!("Foreign key check failed: {e}")),
+    }
+}
+
+fn rebuild_fts(db: &mut Database, log: &mut Vec<String>) {
+    match db.rebuild_fts() {
+        Ok(()) => log.push("Search index rebuilt".to_string()),
+        Err(e) => log.push(format!("Search index rebuild failed: {e}")),
+    }
+}
+
+fn rebuild_tasks(db: &mut Database, log: &mut Vec<String>) {
+    match db.rebuild_task_index() {
+        Ok(count) => log.push(format!("Task index rebuilt: {count} tasks")),
+        Err(e) => log.push(format!("Task index rebuild failed: {e}")),
+    }
+}
+
+fn remove_orphans(db: &mut Database, log: &mut Vec<String>) {
+    match db.remove_orphans() {
+        Ok((links, diffs)) => log.push(format!(
+            "Orphans removed: {links} links, {diffs} history versions")),
+        Err(e) => log.push(format!("Removing orphans failed: {e}")),
+    }
+}
+
+fn vacuum(db: &mut Database, log: &mut Vec<String>) {
+    match db.vacuum() {
+        Ok(()) => log.push("Vacuum done".to_string()),
+        Err(e) => log.push(format!("Vacuum failed: {e}")),
+    }
+}
+
+fn format_size(bytes: i64) -> String {
+    if bytes < 1024 * 1024 {
+        format!("{:.1} KB", bytes as f64 / 1024.0)
+    } else {
+        format!("{:.1} MB", bytes as f64 / (1024.0 * 1024.0))
+    }
+}
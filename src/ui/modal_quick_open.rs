@@ -0,0 +1,247 @@
+use std::collections::HashMap;
+use std::ops::Range;
+use eframe::egui::{self, Key, Modifiers, TextEdit, TextStyle};
+use eframe::egui::text::{LayoutJob, TextFormat};
+use fuzzy_matcher::FuzzyMatcher;
+use fuzzy_matcher::skim::SkimMatcherV2;
+use log::{error};
+use crate::app::{App};
+use crate::db::models::{NoteRef};
+use crate::ui::modal_search::append_highlighted;
+
+const MAX_RESULTS: usize = 50;
+// the most recently updated note gets this bonus, the next one 1 less, ...
+const RECENCY_BONUS: i64 = 30;
+// typing a part of the name should beat a match spread over the path
+const NAME_BONUS: i64 = 20;
+
+#[derive(Debug, Clone)]
+pub struct QuickOpenMatch {
+    pub id: i64,
+    pub path: String, // "Parent / Child / "
+    pub name: String,
+    pub matches: Vec<Range<usize>>, // byte ranges in path + name
+}
+
+impl App {
+    /// Ctrl+P, jump to a note by typing a part of its name
+    pub fn show_quick_open(&mut self, ctx: &egui::Context) {
+        if !self.state_quick_open_load {
+            self.load_quick_open();
+            self.state_quick_open_load = true;
+        }
+
+        let mut open = self.state_quick_open;
+        egui::Window::new("Quick open")
+            .open(&mut open)
+            .title_bar(false)
+            .resizable(false)
+            .collapsible(false)
+            .anchor(egui::Align2::CENTER_TOP, [0.0, 60.0])
+            .show(ctx, |ui| {
+                ui.set_width(450.0);
+
+                // handled before the input, so it doesn't move the cursor
+                let (down, up, enter, escape) = ui.input_mut(|i| (
+                    i.consume_key(Modifiers::NONE, Key::ArrowDown),
+                    i.consume_key(Modifiers::NONE, Key::ArrowUp),
+                    i.consume_key(Modifiers::NONE, Key::Enter),
+                    i.consume_key(Modifiers::NONE, Key::Escape),
+                ));
+
+                let response = ui.add(
+                    TextEdit::singleline(&mut self.quick_open_input)
+                        .hint_text("Go to note")
+                        .desired_width(f32::INFINITY));
+                if self.quick_open_has_focus {
+                    response.request_focus();
+                    self.quick_open_has_focus = false;
+                }
+                if response.changed() {
+                    self.update_quick_open_matches();
+                }
+
+                let count = self.quick_open_matches.len();
+                if down && count > 0 {
+                    self.quick_open_selected = (self.quick_open_selected + 1) % count;
+                }
+                if up && count > 0 {
+                    self.quick_open_selected = (self.quick_open_selected + count - 1) % count;
+                }
+                if escape {
+                    self.close_quick_open();
+                    return;
+                }
+                if enter {
+                    if let Some(x) = self.quick_open_matches.get(self.quick_open_selected) {
+                        self.open_search_result(x.id, false);
+                    }
+                    self.close_quick_open();
+                    return;
+                }
+
+                if count == 0 {
+                    ui.weak("No matching notes");
+                    return;
+                }
+                ui.separator();
+
+                let mut clicked = None;
+                egui::ScrollArea::vertical()
+                    .max_height(300.0)
+                    .show(ui, |ui| {
+                        for (i, x) in self.quick_open_matches.iter().enumerate() {
+                            let selected = i == self.quick_open_selected;
+                            let response = ui.add(egui::SelectableLabel::new(
+                                selected, match_job(ui, x)));
+                            if selected && (up || down) {
+                                response.scroll_to_me(None);
+                            }
+                            if response.clicked() {
+                                clicked = Some(x.id);
+                            }
+                        }
+                    });
+                if let Some(id) = clicked {
+                    self.open_search_result(id, false);
+                    self.close_quick_open();
+                }
+            });
+        if !open {
+            self.close_quick_open();
+        }
+    }
+
+    pub fn open_quick_open(&mut self) {
+        self.state_quick_open = true;
+        self.quick_open_has_focus = true;
+        // notes may have changed since the last time
+        self.state_quick_open_load = false;
+    }
+
+    fn close_quick_open(&mut self) {
+        self.state_quick_open = false;
+        self.quick_open_input.clear();
+        self.quick_open_notes.clear();
+        self.quick_open_matches.clear();
+        self.quick_open_selected = 0;
+    }
+
+    fn load_quick_open(&mut self) {
+        self.quick_open_notes = match crate::db::database::Database::new(&self.db_path)
+            .and_then(|db| db.get_note_refs()) {
+            Ok(x) => x,
+            Err(e) => {
+                error!("Error loading notes for quick open: {e}");
+                Vec::new()
+            }
+        };
+        self.update_quick_open_matches();
+    }
+
+    fn update_quick_open_matches(&mut self) {
+        self.quick_open_matches = rank_notes(&self.quick_open_notes, self.quick_open_input.trim());
+        self.quick_open_selected = 0;
+    }
+}
+
+/// Notes matching the pattern, best first. Empty pattern - most recent notes.
+fn rank_notes(notes: &[NoteRef], pattern: &str) -> Vec<QuickOpenMatch> {
+    let matcher = SkimMatcherV2::default();
+    let by_id: HashMap<i64, &NoteRef> = notes.iter().map(|x| (x.id, x)).collect();
+
+    let mut ranked: Vec<(i64, usize, QuickOpenMatch)> = Vec::new();
+    for (recency, note) in notes.iter().enumerate() {
+        let path = parent_path(note, &by_id);
+        let full = format!("{}{}", path, note.name);
+
+        let (score, indices) = if pattern.is_empty() {
+            (0, Vec::new())
+        } else {
+            let offset = path.chars().count();
+            let by_name = matcher.fuzzy_indices(&note.name, pattern)
+                .map(|(s, i)| (s + NAME_BONUS, i.iter().map(|x| x + offset).collect()));
+            let by_path = matcher.fuzzy_indices(&full, pattern);
+            match (by_name, by_path) {
+                (Some(a), Some(b)) => if a.0 >= b.0 { a } else { b },
+                (Some(a), None) => a,
+                (None, Some(b)) => b,
+                (None, None) => continue,
+            }
+        };
+
+        let bonus = (RECENCY_BONUS - recency as i64).max(0);
+        ranked.push((score + bonus, recency, QuickOpenMatch {
+            id: note.id,
+            matches: char_ranges(&full, &indices),
+            path,
+            name: note.name.clone(),
+        }));
+    }
+
+    ranked.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+    ranked.into_iter()
+        .take(MAX_RESULTS)
+        .map(|(_, _, x)| x)
+        .collect()
+}
+
+/// "Grandparent / Parent / " of the note, empty for top level notes
+fn parent_path(note: &NoteRef, by_id: &HashMap<i64, &NoteRef>) -> String {
+    let mut names = Vec::new();
+    let mut parent_id = note.parent_id;
+    // depth limit guards against broken links forming a cycle
+    while let Some(id) = parent_id && names.len() < 32 {
+        match by_id.get(&id) {
+            Some(parent) => {
+                names.push(parent.name.as_str());
+                parent_id = parent.parent_id;
+            }
+            None => break,
+        }
+    }
+    names.reverse();
+    names.iter().map(|x| format!("{x} / ")).collect()
+}
+
+/// Char indices of the matcher to byte ranges, for highlighting
+fn char_ranges(text: &str, indices: &[usize]) -> Vec<Range<usize>> {
+    text.char_indices()
+        .enumerate()
+        .filter(|(i, _)| indices.contains(i))
+        .map(|(_, (pos, c))| pos..pos + c.len_utf8())
+        .collect()
+}
+
+fn match_job(ui: &egui::Ui, x: &QuickOpenMatch) -> LayoutJob {
+    let style = ui.style();
+    let highlight = style.visuals.selection.bg_fill;
+    let font_id = TextStyle::Body.resolve(style);
+
+    let path_format = TextFormat {
+        font_id: font_id.clone(),
+        color: style.visuals.weak_text_color(),
+        ..Default::default()
+    };
+    let name_format = TextFormat {
+        font_id,
+        color: style.visuals.strong_text_color(),
+        ..Default::default()
+    };
+
+    // split the ranges between the path and the name
+    let split = x.path.len();
+    let path_matches: Vec<Range<usize>> = x.matches.iter()
+        .filter(|r| r.end <= split)
+        .cloned()
+        .collect();
+    let name_matches: Vec<Range<usize>> = x.matches.iter()
+        .filter(|r| r.start >= split)
+        .map(|r| r.start - split..r.end - split)
+        .collect();
+
+    let mut job = LayoutJob::default();
+    append_highlighted(&mut job, &x.path, &path_matches, path_format, highlight);
+    append_highlighted(&mut job, &x.name, &name_matches, name_format, highlight);
+    job
+}
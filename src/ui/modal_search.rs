@@ -1,8 +1,12 @@
-use eframe::egui::{self, TextEdit, Frame, Sense, Label, RichText, Layout, Align};
-use log::{info, error};
-use crate::app::{App};
+use std::ops::Range;
+use eframe::egui::{self, TextEdit, TextStyle};
+use eframe::egui::text::{LayoutJob, TextFormat};
+use crate::app::{App, SidebarTab};
 use crate::constants::RESULT_SUCCESS;
-use crate::ui::custom_button::left_aligned_button;
+use crate::db::models::{SearchResult};
+
+const SEARCH_HINT: &str = "\"phrase\"  word*  -exclude  name:  tag:  parent:  \
+created:>2025-01-01  updated:<7d  in:trash";
 
 impl App {
     pub fn show_search(&mut self, ctx: &egui::Context) { 
@@ -37,67 +41,87 @@ impl App {
                     }
                 });
 
+                if let Some(e) = &self.search_error {
+                    ui.label(egui::RichText::new(e).color(egui::Color32::RED));
+                }
+                ui.label(egui::RichText::new(SEARCH_HINT).small().weak());
+
+                ui.horizontal(|ui| {
+                    ui.add(TextEdit::singleline(&mut self.save_search_name)
+                        .hint_text("Name"));
+                    let can_save = !self.search_input.trim().is_empty()
+                        && !self.save_search_name.trim().is_empty();
+                    if ui.add_enabled(can_save, egui::Button::new("Save search"))
+                        .on_hover_text("Show this search as a folder in the Notes tab")
+                        .clicked()
+                        && let Err(e) = self.try_save_search() {
+                        self.search_error = Some(e.to_string());
+                    }
+                });
+
                 if !self.search_result.is_empty() {
                     ui.separator();
                     
-                    let search = self.search_input.trim();
-                    let mut results_to_display: Vec<(i64, String)> = Vec::new();
-
-                    for note in &self.search_result {
-                            let content = match &note.content {
-                                Some(text) => {
-                                    text.split_whitespace()
-                                        .find(|word| word.to_lowercase().contains(&search))
-                                        .map(|w| w.to_string())
-                                        .unwrap_or_else(|| "No match".to_string())
-                                    }, 
-                                None => String::from("No content"),
-                            };
-                           
-                            let name_match = note.name.to_lowercase().contains(&search);
-                            let content_match = content.to_lowercase().contains(&search);
-                           
-                            if name_match || content_match {
-                            let button_text = match (name_match, content_match) {
-                                (true, true) => format!("{}: {}", note.name, content),
-                                (true, false) => note.name.clone(),
-                                (false, true) => content.clone(),
-                                (false, false) => unreachable!(),
-                            };
-
-                            results_to_display.push((note.id, button_text));
-                            }
-                    }
-                    if results_to_display.is_empty() {
-                        ui.label("No match found");
-                    } else {
+                    let results = self.search_result.clone();
                     egui::ScrollArea::vertical()
-                        .max_height(200.0)
+                        .max_height(300.0)
                         .show(ui, |ui| {
-                            for (id, button_text) in &results_to_display {
-                                ui.horizontal(|ui| {
-                                    ui.with_layout(Layout::left_to_right(Align::Min), |ui| {
-                                        if left_aligned_button(ui, button_text, 450.0, 24.0).clicked() {
-                                            self.selected_index = Some(*id);
-                                            let _ = self.try_get_note(*id);    
-                                        }
-                                    });
-                                });
+                            ui.set_width(450.0);
+                            for x in &results {
+                                let job = result_job(ui, x);
+                                let selected = self.selected_index == Some(x.id);
+                                if ui.add(egui::SelectableLabel::new(selected, job)).clicked() {
+                                    self.open_search_result(x.id, self.search_in_trash);
+                                }
+                                ui.add_space(4.0);
                             }
                         });
-                    }
                 }
         });
         if !open {
             self.state_search = false;
             self.search_input = String::new();
             self.search_has_focus = false;
+            self.search_error = None;
+            self.save_search_name.clear();
+        }
+    }
+
+    /// Opens a note found by search or by a saved search
+    pub fn open_search_result(&mut self, id: i64, in_trash: bool) {
+        // auto-save
+        if self.edited_content != self.original_content {
+            let _ = self.try_auto_update_note_content();
+        }
+        if in_trash {
+            self.selected_tab = SidebarTab::Trash;
+            // the trash preview only shows the selected note
+            self.selected_index = Some(id);
+            let parent_id = crate::db::database::Database::new(&self.db_path)
+                .and_then(|db| db.get_parent_id(id))
+                .unwrap_or_default();
+            let _ = self.try_get_trash_note(id, parent_id);
+            return;
         }
+        self.selected_tab = SidebarTab::Notes;
+        self.selected_index = Some(id);
+        let _ = self.try_get_note(id);
     }
 
     fn try_search(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let query = match crate::search_query::build(&self.search_input) {
+            Ok(x) => x,
+            Err(e) => {
+                self.search_error = Some(e.to_string());
+                self.search_result.clear();
+                return Ok(());
+            }
+        };
+        self.search_error = None;
+        self.search_in_trash = query.in_trash;
+
         let db = crate::db::database::Database::new(&self.db_path)?;
-        match db.search(&self.search_input) {
+        match db.search(&query) {
             Ok(notes) => {
                 self.search_result = notes;
                 self.status_error = String::from(RESULT_SUCCESS);
@@ -111,3 +135,52 @@ impl App {
         Ok(())
     }
 }
+
+/// Name and content fragment of the result with matches highlighted
+fn result_job(ui: &egui::Ui, x: &SearchResult) -> LayoutJob {
+    let style = ui.style();
+    let highlight = style.visuals.selection.bg_fill;
+    let text_color = style.visuals.text_color();
+
+    let name_format = TextFormat {
+        font_id: TextStyle::Body.resolve(style),
+        color: style.visuals.strong_text_color(),
+        ..Default::default()
+    };
+    let snippet_format = TextFormat {
+        font_id: TextStyle::Small.resolve(style),
+        color: text_color,
+        ..Default::default()
+    };
+
+    let mut job = LayoutJob::default();
+    append_highlighted(&mut job, &x.name, &x.name_matches, name_format, highlight);
+    if !x.snippet.trim().is_empty() {
+        job.append("\n", 0.0, TextFormat::default());
+        // fragments may span several lines, keep the list compact
+        let snippet = x.snippet.replace('\n', " ");
+        append_highlighted(&mut job, &snippet, &x.snippet_matches, snippet_format, highlight);
+    }
+    job
+}
+
+pub fn append_highlighted(
+    job: &mut LayoutJob,
+    text: &str,
+    matches: &[Range<usize>],
+    format: TextFormat,
+    highlight: egui::Color32) {
+    let mut pos = 0;
+    for m in matches {
+        if m.start < pos || m.end > text.len() {
+            continue;
+        }
+        job.append(&text[pos..m.start], 0.0, format.clone());
+        job.append(&text[m.clone()], 0.0, TextFormat {
+            background: highlight,
+            ..format.clone()
+        });
+        pos = m.end;
+    }
+    job.append(&text[pos..], 0.0, format);
+}
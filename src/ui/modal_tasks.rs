@@ -0,0 +1,165 @@
+use eframe::egui::{self, RichText};
+use log::{error};
+use crate::app::{App, TaskFilter, TaskGrouping};
+use crate::db::models::{Task};
+
+const NO_TAG: &str = "No tag";
+
+impl App {
+    /// Task items of all notes, toggled in place without opening the note
+    pub fn show_tasks(&mut self, ctx: &egui::Context) {
+        if !self.state_tasks_load {
+            self.tasks = match crate::db::database::Database::new(&self.db_path)
+                .and_then(|db| db.get_tasks()) {
+                Ok(x) => x,
+                Err(e) => {
+                    error!("Error loading tasks: {e}");
+                    Vec::new()
+                }
+            };
+            self.state_tasks_load = true;
+        }
+
+        let mut open = self.state_tasks_open;
+        let mut toggle = None;
+        let mut open_note = None;
+        egui::Window::new("Tasks")
+            .open(&mut open)
+            .default_width(450.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Group by:");
+                    ui.selectable_value(&mut self.task_grouping, TaskGrouping::Note, "Note");
+                    ui.selectable_value(&mut self.task_grouping, TaskGrouping::Tag, "Tag");
+                    ui.separator();
+                    ui.label("Show:");
+                    ui.selectable_value(&mut self.task_filter, TaskFilter::Open, "Open");
+                    ui.selectable_value(&mut self.task_filter, TaskFilter::Done, "Done");
+                    ui.selectable_value(&mut self.task_filter, TaskFilter::All, "All");
+                });
+                ui.separator();
+
+                let groups = group_tasks(&self.tasks, self.task_grouping, self.task_filter);
+                if groups.is_empty() {
+                    ui.weak("No tasks, add \"- [ ] something\" to a note");
+                    return;
+                }
+
+                let today = chrono::Local::now().date_naive().format("%Y-%m-%d").to_string();
+                egui::ScrollArea::vertical()
+                    .max_height(400.0)
+                    .show(ui, |ui| {
+                        for (title, note_id, tasks) in &groups {
+                            let response = egui::CollapsingHeader::new(
+                                RichText::new(format!("{title} ({})", tasks.len())).strong())
+                                .id_salt(("task_group", title, note_id))
+                                .default_open(true)
+                                .show(ui, |ui| {
+                                    for task in tasks {
+                                        ui.horizontal_wrapped(|ui| {
+                                            let mut done = task.done;
+                                            if ui.checkbox(&mut done, "").changed() {
+                                                toggle = Some((*task).clone());
+                                            }
+                                            ui.label(&task.text);
+                                            if let Some(due) = &task.due {
+                                                let color = if !task.done && *due < today {
+                                                    egui::Color32::RED
+                                                } else {
+                                                    ui.visuals().weak_text_color()
+                                                };
+                                                ui.label(RichText::new(format!("📅 {due}")).small().color(color));
+                                            }
+                                            // in tag groups the note isn't in the header
+                                            if note_id.is_none()
+                                                && ui.link(RichText::new(&task.note_name).small()).clicked() {
+                                                open_note = Some(task.note_id);
+                                            }
+                                        });
+                                    }
+                                });
+                            if let Some(id) = note_id {
+                                response.header_response.context_menu(|ui| {
+                                    if ui.button("Open note").clicked() {
+                                        open_note = Some(*id);
+                                        ui.close_menu();
+                                    }
+                                });
+                            }
+                        }
+                    });
+            });
+
+        if let Some(task) = toggle {
+            let res = self.try_toggle_task(&task);
+            self.status_error = crate::utils::result(res, "Error toggling task");
+        }
+        if let Some(id) = open_note {
+            self.open_search_result(id, false);
+        }
+        if !open {
+            self.state_tasks_open = false;
+        }
+    }
+
+    /// Flips the item in its note and saves the note.
+    /// The open note is changed in the editor and saved the usual way.
+    fn try_toggle_task(&mut self, task: &Task) -> Result<(), Box<dyn std::error::Error>> {
+        self.state_tasks_load = false;
+
+        if self.edited_note_id == Some(task.note_id) {
+            // positions are of the saved content
+            if self.should_save() {
+                return Err("save the note first".into());
+            }
+            crate::markdown::toggle_task(&mut self.edited_content, task.position);
+            return self.try_update_note_content();
+        }
+
+        let mut db = crate::db::database::Database::new(&self.db_path)?;
+        let old = db.get_note(task.note_id)?.content.unwrap_or_default();
+        let mut new = old.clone();
+        crate::markdown::toggle_task(&mut new, task.position);
+        if new == old {
+            // index is out of date, it's reloaded above
+            return Ok(());
+        }
+        db.update_note_content(task.note_id, &new)?;
+        db.insert_note_diff(task.note_id, &crate::diff::get_diff_json(&old, &new))?;
+        self.saved_search_results.clear();
+        self.embed_cache.clear();
+        self.state_calendar_load = false;
+        Ok(())
+    }
+}
+
+/// (title, note id of note groups, tasks) in display order.
+/// An item with several tags is in several tag groups.
+fn group_tasks(tasks: &[Task], grouping: TaskGrouping, filter: TaskFilter) -> Vec<(String, Option<i64>, Vec<&Task>)> {
+    let mut groups: Vec<(String, Option<i64>, Vec<&Task>)> = Vec::new();
+    let shown = tasks.iter().filter(|x| match filter {
+        TaskFilter::Open => !x.done,
+        TaskFilter::Done => x.done,
+        TaskFilter::All => true,
+    });
+
+    for task in shown {
+        let keys: Vec<(String, Option<i64>)> = match grouping {
+            TaskGrouping::Note => vec![(task.note_name.clone(), Some(task.note_id))],
+            TaskGrouping::Tag if task.tags.is_empty() => vec![(NO_TAG.to_string(), None)],
+            TaskGrouping::Tag => task.tags.iter().map(|x| (format!("#{x}"), None)).collect(),
+        };
+        for (title, note_id) in keys {
+            match groups.iter_mut().find(|(t, n, _)| *t == title && *n == note_id) {
+                Some((_, _, x)) => x.push(task),
+                None => groups.push((title, note_id, vec![task])),
+            }
+        }
+    }
+
+    // notes stay in the order of the last update, tags are sorted
+    if grouping == TaskGrouping::Tag {
+        groups.sort_by(|a, b| (a.0 == NO_TAG, &a.0).cmp(&(b.0 == NO_TAG, &b.0)));
+    }
+    groups
+}
@@ -0,0 +1,145 @@
+use eframe::egui::{self, RichText};
+use log::{error};
+use crate::app::{App};
+use crate::actions::find_note;
+
+const VARIABLES_HELP: &str = "Variables: {{title}} {{parent}} {{date}} {{time}}\n\
+    {{date:%d.%m.%Y}} takes a date format,\n\
+    {{?Field}} is asked for when the note is added";
+
+impl App {
+    pub fn load_templates(&mut self) {
+        if self.state_templates_load {
+            return;
+        }
+        self.templates = match crate::db::database::Database::new(&self.db_path)
+            .and_then(|db| db.get_templates()) {
+            Ok(x) => x,
+            Err(e) => {
+                error!("Error loading templates: {e}");
+                Vec::new()
+            }
+        };
+        self.state_templates_load = true;
+    }
+
+    /// Templates of new notes, written here or taken from the open note
+    pub fn show_templates(&mut self, ctx: &egui::Context) {
+        self.load_templates();
+
+        let mut open = self.state_templates_open;
+        egui::Window::new("Templates")
+            .open(&mut open)
+            .default_width(600.0)
+            .show(ctx, |ui| {
+                ui.horizontal_top(|ui| {
+                    ui.vertical(|ui| {
+                        ui.set_width(150.0);
+                        self.show_template_list(ui);
+                    });
+                    ui.separator();
+                    ui.vertical(|ui| {
+                        self.show_template_editor(ui);
+                    });
+                });
+            });
+        self.state_templates_open = open;
+    }
+
+    fn show_template_list(&mut self, ui: &mut egui::Ui) {
+        if ui.button("➕ New").clicked() {
+            self.edit_template(None, String::new(), String::new());
+        }
+        let note = self.selected_index
+            .and_then(|x| find_note(&self.names, x))
+            .filter(|x| !x.is_encrypted);
+        let response = ui.add_enabled(note.is_some(), egui::Button::new("📄 From open note"))
+            .on_hover_text("New template with the content of the open note")
+            .on_disabled_hover_text("Open a note that isn't encrypted");
+        if response.clicked()
+            && let Some(name) = note.map(|x| x.name.clone()) {
+            self.edit_template(None, name, self.edited_content.clone());
+        }
+        ui.separator();
+
+        if self.templates.is_empty() {
+            ui.weak("No templates yet");
+            return;
+        }
+        let mut clicked = None;
+        egui::ScrollArea::vertical()
+            .id_salt("template list")
+            .max_height(300.0)
+            .show(ui, |ui| {
+                for template in &self.templates {
+                    let selected = self.template_edit_id == Some(template.id);
+                    if ui.selectable_label(selected, &template.name).clicked() {
+                        clicked = Some(template.clone());
+                    }
+                }
+            });
+        if let Some(x) = clicked {
+            self.edit_template(Some(x.id), x.name, x.content);
+        }
+    }
+
+    fn show_template_editor(&mut self, ui: &mut egui::Ui) {
+        if let Some(e) = &self.template_error {
+            ui.label(RichText::new(e).color(egui::Color32::RED));
+        }
+        ui.horizontal(|ui| {
+            ui.label("Name:");
+            ui.text_edit_singleline(&mut self.template_name_input);
+        });
+        egui::ScrollArea::vertical()
+            .id_salt("template content")
+            .max_height(300.0)
+            .show(ui, |ui| {
+                ui.add(egui::TextEdit::multiline(&mut self.template_content_input)
+                    .code_editor()
+                    .desired_rows(12)
+                    .desired_width(f32::INFINITY));
+            });
+        ui.weak(VARIABLES_HELP);
+
+        ui.horizontal(|ui| {
+            let name = self.template_name_input.trim().to_string();
+            if ui.add_enabled(!name.is_empty(), egui::Button::new("Save")).clicked() {
+                let res = self.try_save_template(&name);
+                self.template_error = res.err().map(|e| format!("Error saving template: {e}"));
+            }
+            if let Some(id) = self.template_edit_id
+                && ui.button("Delete").clicked() {
+                let res = self.try_delete_template(id);
+                self.template_error = res.err().map(|e| format!("Error deleting template: {e}"));
+            }
+        });
+    }
+
+    fn edit_template(&mut self, id: Option<i64>, name: String, content: String) {
+        self.template_edit_id = id;
+        self.template_name_input = name;
+        self.template_content_input = content;
+        self.template_error = None;
+    }
+
+    fn try_save_template(&mut self, name: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let mut db = crate::db::database::Database::new(&self.db_path)?;
+        let id = db.save_template(self.template_edit_id, name, &self.template_content_input)?;
+        self.templates = db.get_templates()?;
+        self.template_edit_id = Some(id);
+        self.template_name_input = name.to_string();
+        Ok(())
+    }
+
+    fn try_delete_template(&mut self, id: i64) -> Result<(), Box<dyn std::error::Error>> {
+        let mut db = crate::db::database::Database::new(&self.db_path)?;
+        db.delete_template(id)?;
+        if self.add_new_note_template == Some(id) {
+            self.add_new_note_template = None;
+        }
+        self.edit_template(None, String::new(), String::new());
+        self.state_templates_load = false;
+        Ok(())
+    }
+}
@@ -1,5 +1,5 @@
 use std::error::Error;
-use log::{info, error};
+use log::{error};
 use crate::db::models::{NoteIdName};
 use eframe::egui::{self, RichText};
 use crate::app::{App};
@@ -13,6 +13,10 @@ impl App {
             match db.get_notes() {
                 Ok(notes) => {
                     self.names = notes;
+                    self.saved_search_results.clear();
+                    self.embed_cache.clear();
+                    self.state_calendar_load = false;
+                    self.state_tasks_load = false;
                     self.load_rows = true; // TODO: move to state
                 }
                 Err(e) => {
@@ -22,30 +26,46 @@ impl App {
             }
         }
 
-        if self.names.is_empty() {
-            ui.label("No notes found");
-        } else {
-            egui::ScrollArea::vertical()
-                .auto_shrink([false; 2])
-                .show(ui, |ui| {
+        egui::ScrollArea::vertical()
+            .auto_shrink([false; 2])
+            .show(ui, |ui| {
+            self.show_saved_searches(ui);
+            if self.names.is_empty() {
+                ui.label("No notes found");
+            } else {
                 self.draw_note_tree(ui);
-            });
-        }
+            }
+        });
         Ok(()) 
     }
     
     pub fn try_get_note(&mut self, id: i64) -> Result<(), Box<dyn std::error::Error>> {
         let db = crate::db::database::Database::new(&self.db_path)?;
         let note = db.get_note(id)?;
-        println!("{:?}", note);
-        self.original_content = note.content.clone()
-            .unwrap_or("".to_string());
-        self.edited_content = note.content.unwrap_or("".to_string()); 
+        let mut content = note.content.unwrap_or("".to_string());
+
+        if crate::crypto::is_encrypted(&content) {
+            match self.note_keys.get(&id) {
+                Some(key) => content = crate::crypto::decrypt(key, &content)?,
+                None => {
+                    // locked until the passphrase is entered
+                    self.locked_note_id = Some(id);
+                    self.original_content = String::new();
+                    self.edited_content = String::new();
+                    self.edited_note_id = None;
+                    return Ok(());
+                }
+            }
+        }
+
+        self.locked_note_id = None;
+        self.original_content = content.clone();
+        self.edited_content = content;
         self.edited_note_id = Some(id);
         Ok(())
     }
     
-    fn try_delete_note(&mut self, id: i64) -> Result<(), Box<dyn std::error::Error>> {
+    pub fn try_delete_note(&mut self, id: i64) -> Result<(), Box<dyn std::error::Error>> {
         println!("id: {:?}", id);
         let mut db = crate::db::database::Database::new(&self.db_path)?;
         self.status_error = crate::utils::result(
@@ -67,11 +87,14 @@ impl App {
 
     fn draw_note(&mut self, ui: &mut egui::Ui, note: &NoteIdName) {
         let is_selected = Some(note.id) == self.selected_index;
-        let display_name = if is_selected && self.edited_content != self.original_content {
+        let mut display_name = if is_selected && self.edited_content != self.original_content {
             format!("* {}", note.name)
         } else {
             note.name.clone()
         };
+        if note.is_encrypted {
+            display_name = format!("🔒 {}", display_name);
+        }
 
         if note.children.is_empty() {
             let response = ui.add(egui::SelectableLabel::new(is_selected, &display_name));
@@ -98,32 +121,7 @@ impl App {
 
             // right btn menu
             response.context_menu(|ui| {
-                if !note.has_parent {
-                    // add a child note, selected note is parent
-                    if ui.button("Add child note").clicked() {
-                        self.state_add_new_note = true;
-                        // parent id
-                        self.parent_note_id = Some(note.id);
-                        ui.close_menu();
-                    }
-                }
-
-                if ui.button("Rename").clicked() {
-                    info!("Rename clicked with id: {}", note.id);
-                    self.rename_input = note.name.to_string();
-                    self.selected_index = Some(note.id);
-                    // show popup with name as input
-                    self.state_rename = true;
-                    ui.close_menu();
-                }
-
-                if ui.button("Delete").clicked() {
-                    info!("Delete clicked");
-                    let _ = self.try_delete_note(note.id);
-                    self.original_content = String::new();
-                    self.edited_content = String::new();
-                    ui.close_menu();
-                }
+                self.note_context_menu(ui, note);
             });
         } else {
             // get selection color from the theme
@@ -160,30 +158,7 @@ impl App {
             }
 
             response.header_response.context_menu(|ui| {
-                // add a child note, selected note is parent
-                if ui.button("Add child note").clicked() {
-                    self.state_add_new_note = true;
-                    // parent id
-                    self.parent_note_id = Some(note.id);
-                    ui.close_menu();
-                }
-
-                if ui.button("Rename").clicked() {
-                    info!("Rename clicked with id: {}", note.id);
-                    self.rename_input = note.name.to_string();
-                    self.selected_index = Some(note.id);
-                    // show popup with name as input
-                    self.state_rename = true;
-                    ui.close_menu();
-                }
-
-                if ui.button("Delete").clicked() {
-                    info!("Delete clicked");
-                    let _ = self.try_delete_note(note.id);
-                    self.original_content = String::new();
-                    self.edited_content = String::new();
-                    ui.close_menu();
-                }
+                self.note_context_menu(ui, note);
             });
         }
     }
@@ -195,11 +170,16 @@ impl App {
     pub fn try_update_note_content(&mut self) -> Result<(), Box<dyn std::error::Error>> {
         if let Some(id) = self.selected_index && self.should_save() {
             let mut db = crate::db::database::Database::new(&self.db_path)?;
-            match db.update_note_content(id, &self.edited_content) {
+            let content = self.content_for_db(id, &self.edited_content)?;
+            match db.update_note_content(id, &content) {
                 Ok(_) => {
                     let _ = self.try_save_note_diff(id);
                     println!("Saved successfully!");
                     self.original_content = self.edited_content.clone();
+                    self.saved_search_results.clear();
+                    self.embed_cache.clear();
+                    self.state_calendar_load = false;
+                    self.state_tasks_load = false;
                 }
                 Err(e) => println!("Failed to save: {e}"),
             } 
@@ -216,13 +196,18 @@ impl App {
         let autosave = self.config.autosave.unwrap_or(true);
         if self.edited_note_id.is_some() && autosave && self.should_save() {
             let mut db = crate::db::database::Database::new(&self.db_path)?;
-            match db.update_note_content(self.edited_note_id.unwrap(),  &self.edited_content) {
+            let content = self.content_for_db(self.edited_note_id.unwrap(), &self.edited_content)?;
+            match db.update_note_content(self.edited_note_id.unwrap(), &content) {
                 Ok(_) => {
                     let _ = self.try_save_note_diff(self.edited_note_id.unwrap());
                     println!("Saved successfully!");
                     self.original_content = String::new(); 
                     self.edited_content = String::new();
                     self.edited_note_id = None;
+                    self.saved_search_results.clear();
+                    self.embed_cache.clear();
+                    self.state_calendar_load = false;
+                    self.state_tasks_load = false;
                 }
                 Err(e) => println!("Failed to save: {e}"),
             } 
@@ -242,6 +227,8 @@ impl App {
         let json = crate::diff::get_diff_json(
             &self.original_content,
             &self.edited_content);
+        // history of an encrypted note is encrypted too
+        let json = self.content_for_db(note_id, &json)?;
         let mut db = crate::db::database::Database::new(&self.db_path)?;
         match db.insert_note_diff(note_id, &json) {
             Ok(_) => {
@@ -251,4 +238,12 @@ impl App {
         }
         Ok(())
     }
+
+    /// Encrypts content of an encrypted note, other notes are stored as is
+    pub fn content_for_db(&self, id: i64, content: &str) -> Result<String, String> {
+        match self.note_keys.get(&id) {
+            Some(key) => crate::crypto::encrypt(key, content),
+            None => Ok(content.to_string()),
+        }
+    }
 }
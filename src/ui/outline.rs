@@ -0,0 +1,69 @@
+use eframe::egui::{self, RichText};
+use eframe::egui::text::CCursor;
+use eframe::egui::text_selection::CCursorRange;
+use crate::app::{App};
+use crate::markdown::{outline, scroll_to_anchor};
+
+const LEVEL_INDENT: f32 = 12.0;
+
+impl App {
+    /// Headings of the open note, a click jumps the editor and the preview there
+    pub fn show_outline(&mut self, ctx: &egui::Context) {
+        egui::SidePanel::right("outline panel")
+            .resizable(true)
+            .default_width(200.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.strong("Outline");
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        if ui.small_button("✖").on_hover_text("Hide outline").clicked() {
+                            self.state_outline_open = false;
+                        }
+                    });
+                });
+                ui.separator();
+
+                let locked = self.locked_note_id.is_some() && self.locked_note_id == self.selected_index;
+                if self.selected_index.is_none() || locked {
+                    ui.weak("No note is open");
+                    return;
+                }
+                let entries = outline(&self.edited_content, self.markdown_extensions.0);
+                if entries.is_empty() {
+                    ui.weak("No headings");
+                    return;
+                }
+
+                egui::ScrollArea::vertical()
+                    .auto_shrink([false; 2])
+                    .show(ui, |ui| {
+                        let top = entries.iter().map(|x| x.level).min().unwrap_or(1);
+                        for entry in &entries {
+                            ui.horizontal(|ui| {
+                                ui.add_space((entry.level - top) as f32 * LEVEL_INDENT);
+                                let mut title = RichText::new(&entry.title);
+                                if entry.level == top {
+                                    title = title.strong();
+                                }
+                                if ui.selectable_label(false, title).clicked() {
+                                    scroll_to_anchor(ctx, &entry.anchor);
+                                    self.editor_jump = Some(entry.offset);
+                                }
+                            });
+                        }
+                    });
+            });
+    }
+
+    /// Moves the editor cursor to the offset and scrolls it into view
+    pub fn jump_editor(&self, ui: &mut egui::Ui, output: &mut egui::text_edit::TextEditOutput, offset: usize) {
+        let index = self.edited_content.get(..offset).map_or(0, |x| x.chars().count());
+        let ccursor = CCursor::new(index);
+        output.state.cursor.set_char_range(Some(CCursorRange::one(ccursor)));
+        output.state.clone().store(ui.ctx(), output.response.id);
+        output.response.request_focus();
+
+        let rect = output.galley.pos_from_ccursor(ccursor).translate(output.galley_pos.to_vec2());
+        ui.scroll_to_rect(rect, Some(egui::Align::TOP));
+    }
+}
@@ -0,0 +1,111 @@
+use eframe::egui::{self, RichText};
+use log::{info, error};
+use crate::app::{App};
+use crate::db::models::{SavedSearch};
+
+impl App {
+    /// Smart folders on top of the note tree, each one re-runs its query
+    pub fn show_saved_searches(&mut self, ui: &mut egui::Ui) {
+        if !self.state_saved_searches_load {
+            self.saved_searches = match crate::db::database::Database::new(&self.db_path)
+                .and_then(|db| db.get_saved_searches()) {
+                Ok(x) => x,
+                Err(e) => {
+                    error!("Error loading saved searches: {e}");
+                    Vec::new()
+                }
+            };
+            self.saved_search_results.clear();
+            self.state_saved_searches_load = true;
+        }
+
+        if self.saved_searches.is_empty() {
+            return;
+        }
+
+        for saved in &self.saved_searches.clone() {
+            self.draw_saved_search(ui, saved);
+        }
+        ui.separator();
+    }
+
+    fn draw_saved_search(&mut self, ui: &mut egui::Ui, saved: &SavedSearch) {
+        let header = egui::CollapsingHeader::new(RichText::new(format!("🔍 {}", saved.name)).italics())
+            .id_salt(("saved_search", saved.id))
+            .default_open(false);
+
+        let response = header.show(ui, |ui| {
+            // results are dropped whenever notes change, run again on the next frame
+            if !self.saved_search_results.contains_key(&saved.id) {
+                let result = self.run_saved_search(&saved.query);
+                self.saved_search_results.insert(saved.id, result);
+            }
+
+            match self.saved_search_results.get(&saved.id).cloned() {
+                Some(Ok((in_trash, results))) => {
+                    if results.is_empty() {
+                        ui.weak("No matches");
+                    }
+                    for x in &results {
+                        let selected = !in_trash && self.selected_index == Some(x.id);
+                        if ui.add(egui::SelectableLabel::new(selected, &x.name)).clicked() {
+                            self.open_search_result(x.id, in_trash);
+                        }
+                    }
+                }
+                Some(Err(e)) => {
+                    ui.label(RichText::new(e).color(egui::Color32::RED));
+                }
+                None => {}
+            }
+        });
+
+        response.header_response.on_hover_text(&saved.query).context_menu(|ui| {
+            if ui.button("Edit in search").clicked() {
+                self.search_input = saved.query.clone();
+                self.save_search_name = saved.name.clone();
+                self.state_search = true;
+                self.search_has_focus = true;
+                ui.close_menu();
+            }
+            if ui.button("Delete").clicked() {
+                info!("Delete saved search clicked with id: {}", saved.id);
+                let _ = self.try_delete_saved_search(saved.id);
+                ui.close_menu();
+            }
+        });
+    }
+
+    fn run_saved_search(&self, query: &str) -> crate::app::SmartFolderResult {
+        let query = crate::search_query::build(query).map_err(|e| e.to_string())?;
+        let db = crate::db::database::Database::new(&self.db_path).map_err(|e| e.to_string())?;
+        let results = db.search(&query).map_err(|e| e.to_string())?;
+        Ok((query.in_trash, results))
+    }
+
+    pub fn try_save_search(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        // only valid queries are saved
+        crate::search_query::build(&self.search_input)?;
+
+        let mut db = crate::db::database::Database::new(&self.db_path)?;
+        self.status_error = crate::utils::result(
+            db.save_search(self.save_search_name.trim(), self.search_input.trim()),
+            "Error saving search");
+
+        self.save_search_name.clear();
+        // refresh ui
+        self.state_saved_searches_load = false;
+        Ok(())
+    }
+
+    fn try_delete_saved_search(&mut self, id: i64) -> Result<(), Box<dyn std::error::Error>> {
+        let mut db = crate::db::database::Database::new(&self.db_path)?;
+        self.status_error = crate::utils::result(
+            db.delete_saved_search(id),
+            "Error deleting saved search");
+
+        // refresh ui
+        self.state_saved_searches_load = false;
+        Ok(())
+    }
+}
@@ -1,9 +1,12 @@
 use eframe::egui::{self, ComboBox};
 use rfd::FileDialog;
 use std::path::Path;
+use crate::actions::Action;
 use crate::app::{App};
+use crate::keymap::{self, Keymap};
+use crate::markdown::{EXTENSIONS, EXTENSIONS_SETTING};
 use crate::ui::toggle_compact::toggle;
-use crate::constants::{DEFAULT_IS_DARK_MODE, DEFAULT_FONT, DEFAULT_FONT_SIZE};
+use crate::constants::{DEFAULT_IS_DARK_MODE, DEFAULT_FONT, DEFAULT_FONT_SIZE, DEFAULT_TRASH_RETENTION_DAYS};
 
 impl App {
     pub fn show_font_settings(&mut self, ctx: &egui::Context) {
@@ -121,6 +124,30 @@ impl App {
                         self.config.save_config();
                     }
 
+                    ui.separator();
+                    // Trash
+                    ui.horizontal(|ui| {
+                        ui.label("Keep notes in the trash (days, 0 - forever):");
+                        let mut days = self.config.trash_retention_days
+                            .unwrap_or(DEFAULT_TRASH_RETENTION_DAYS);
+                        if ui.add(egui::DragValue::new(&mut days).range(0..=3650)).changed() {
+                            self.config.trash_retention_days = Some(days);
+                            self.config.save_config();
+                        }
+                    });
+
+                    ui.separator();
+                    self.show_backup_settings(ui);
+
+                    ui.separator();
+                    self.show_markdown_settings(ui);
+
+                    ui.separator();
+                    self.show_journal_settings(ui);
+
+                    ui.separator();
+                    self.show_keybinding_settings(ui);
+
                     ui.separator();
 
                     if ui.button("Close").clicked() {
@@ -133,6 +160,224 @@ impl App {
         }
     }
 
+    /// Extensions are stored in the archive, not in the config
+    fn show_markdown_settings(&mut self, ui: &mut egui::Ui) {
+        let mut changed = false;
+
+        ui.label("Markdown extensions (this archive):");
+        egui::Grid::new("markdown_settings_grid").show(ui, |ui| {
+            for (i, (_, title, options)) in EXTENSIONS.iter().enumerate() {
+                let mut enabled = self.markdown_extensions.0.contains(*options);
+                if ui.checkbox(&mut enabled, *title).changed() {
+                    self.markdown_extensions.0.set(*options, enabled);
+                    changed = true;
+                }
+                if i % 2 == 1 {
+                    ui.end_row();
+                }
+            }
+        });
+
+        if changed {
+            let res = self.try_save_markdown_extensions();
+            self.status_error = crate::utils::result(res, "Error saving markdown extensions");
+        }
+    }
+
+    fn try_save_markdown_extensions(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let mut db = crate::db::database::Database::new(&self.db_path)?;
+        db.set_setting(EXTENSIONS_SETTING, &self.markdown_extensions.to_setting())?;
+        Ok(())
+    }
+
+    /// Stored in the archive like the markdown extensions
+    fn show_journal_settings(&mut self, ui: &mut egui::Ui) {
+        self.load_templates();
+        let mut changed = false;
+
+        ui.label("Journal (this archive):");
+        egui::Grid::new("journal_settings_grid").show(ui, |ui| {
+            ui.label("Parent note:");
+            let response = ui.text_edit_singleline(&mut self.journal.parent)
+                .on_hover_text("Top level note of the daily entries, made when missing");
+            changed |= response.lost_focus() && !self.journal.parent.trim().is_empty();
+            ui.end_row();
+
+            ui.label("Template:");
+            let selected = match self.journal.template.as_str() {
+                "" => "None",
+                x => x,
+            };
+            ComboBox::from_id_salt("journal_template")
+                .selected_text(selected.to_string())
+                .show_ui(ui, |ui| {
+                    changed |= ui.selectable_value(&mut self.journal.template, String::new(), "None").changed();
+                    for template in &self.templates {
+                        changed |= ui.selectable_value(
+                            &mut self.journal.template, template.name.clone(), &template.name).changed();
+                    }
+                });
+            ui.end_row();
+        });
+
+        if changed {
+            let res = self.try_save_journal_settings();
+            self.status_error = crate::utils::result(res, "Error saving journal settings");
+        }
+    }
+
+    fn try_save_journal_settings(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let mut db = crate::db::database::Database::new(&self.db_path)?;
+        self.journal.save(&mut db)?;
+        Ok(())
+    }
+
+    fn show_backup_settings(&mut self, ui: &mut egui::Ui) {
+        let mut changed = false;
+
+        ui.label("Backups:");
+        ui.horizontal(|ui| {
+            changed |= toggle(ui, &mut self.config.backup.enabled).changed();
+            ui.label("Enabled");
+            ui.add_space(10.0);
+            changed |= toggle(ui, &mut self.config.backup.on_open).changed();
+            ui.label("On open");
+        });
+
+        // backups location
+        ui.horizontal(|ui| {
+            let mut dir = self.config.backup_dir()
+                .map(|x| x.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            ui.add_enabled(false, egui::TextEdit::singleline(&mut dir));
+
+            if ui.button("...").clicked() {
+                if let Some(path) = FileDialog::new()
+                    .set_title("Select dir for backups")
+                    .pick_folder() {
+                    self.config.backup.dir = Some(path);
+                    self.state_backups_load = false;
+                    changed = true;
+                } else {
+                    eprintln!("No directory selected");
+                }
+            }
+        });
+
+        egui::Grid::new("backup_settings_grid").show(ui, |ui| {
+            ui.label("Every (hours, 0 - off):");
+            changed |= ui.add(egui::DragValue::new(&mut self.config.backup.interval_hours)
+                .range(0..=168)).changed();
+            ui.end_row();
+
+            ui.label("Keep daily:");
+            changed |= ui.add(egui::DragValue::new(&mut self.config.backup.keep_daily)
+                .range(0..=365)).changed();
+            ui.end_row();
+
+            ui.label("Keep weekly:");
+            changed |= ui.add(egui::DragValue::new(&mut self.config.backup.keep_weekly)
+                .range(0..=104)).changed();
+            ui.end_row();
+        });
+
+        if changed {
+            self.config.save_config();
+        }
+    }
+
+    fn show_keybinding_settings(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("Keyboard shortcuts")
+            .default_open(false)
+            .show(ui, |ui| {
+                ui.label(egui::RichText::new("e.g. Ctrl+Shift+P, Alt+Down, F2. Empty - no shortcut.")
+                    .small().weak());
+
+                let mut changed = None;
+                egui::ScrollArea::vertical()
+                    .max_height(250.0)
+                    .show(ui, |ui| {
+                        egui::Grid::new("keybindings_grid")
+                            .num_columns(3)
+                            .striped(true)
+                            .show(ui, |ui| {
+                                for action in Action::ALL {
+                                    ui.label(format!("{}: {}", action.menu().0.title(), action.title()));
+
+                                    let input = self.keybinding_inputs.entry(action)
+                                        .or_insert_with(|| keymap::binding(&self.config.keybindings, action));
+                                    let mut edit = egui::TextEdit::singleline(input)
+                                        .desired_width(140.0);
+                                    if self.keymap.is_conflict(action)
+                                        || self.keybinding_errors.contains_key(&action) {
+                                        edit = edit.text_color(egui::Color32::RED);
+                                    }
+                                    if ui.add(edit).lost_focus() {
+                                        changed = Some(action);
+                                    }
+
+                                    let is_default = self.config.keybindings.as_ref()
+                                        .is_none_or(|x| !x.contains_key(action.id()));
+                                    if ui.add_enabled(!is_default, egui::Button::new("Reset")).clicked() {
+                                        self.keybinding_inputs.insert(action,
+                                            action.default_binding().unwrap_or_default().to_string());
+                                        changed = Some(action);
+                                    }
+                                    ui.end_row();
+                                }
+                            });
+                    });
+
+                if let Some(action) = changed {
+                    self.apply_keybinding(action);
+                }
+
+                for (chord, actions) in &self.keymap.conflicts {
+                    let names: Vec<&str> = actions.iter().map(|x| x.title()).collect();
+                    ui.label(egui::RichText::new(
+                        format!("{chord} is used by: {}. None of them will run.", names.join(", ")))
+                        .color(egui::Color32::RED));
+                }
+                for (action, e) in &self.keybinding_errors {
+                    ui.label(egui::RichText::new(format!("{}: {e}", action.title()))
+                        .color(egui::Color32::RED));
+                }
+            });
+    }
+
+    /// Validates the edited shortcut, stores it in config.toml if it differs from the default
+    fn apply_keybinding(&mut self, action: Action) {
+        let input = self.keybinding_inputs.get(&action).cloned().unwrap_or_default();
+        let chord = match input.trim() {
+            "" => String::new(),
+            x => match keymap::parse_shortcut(x) {
+                Ok(shortcut) => keymap::format_shortcut(&shortcut),
+                Err(e) => {
+                    self.keybinding_errors.insert(action, e);
+                    return;
+                }
+            },
+        };
+        self.keybinding_errors.remove(&action);
+        self.keybinding_inputs.insert(action, chord.clone());
+
+        let overrides = self.config.keybindings.get_or_insert_with(Default::default);
+        let default = action.default_binding()
+            .and_then(|x| keymap::parse_shortcut(x).ok())
+            .map(|x| keymap::format_shortcut(&x))
+            .unwrap_or_default();
+        if chord == default {
+            overrides.remove(action.id());
+        } else {
+            overrides.insert(action.id().to_string(), chord);
+        }
+        if overrides.is_empty() {
+            self.config.keybindings = None;
+        }
+        self.config.save_config();
+        self.keymap = Keymap::new(&self.config.keybindings);
+    }
+
     pub fn apply_font_size(&self, ctx: &egui::Context) {
         let mut style = (*ctx.style()).clone();
 
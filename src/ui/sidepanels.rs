@@ -1,6 +1,9 @@
 use eframe::egui;
+use crate::actions::Action;
 use crate::app::{App, SidebarTab};
-use crate::markdown::render_md;
+use crate::embeds::{Embeds, find_note, note_name};
+use crate::markdown::{render_md, toggle_task};
+use crate::markdown_highlight::highlight_md;
 
 impl App {
     pub fn show_sidepanels(&mut self, ctx: &egui::Context) {
@@ -12,14 +15,10 @@ impl App {
                     // Tabs
                     ui.horizontal(|ui| {
                         if ui.selectable_label(self.selected_tab == SidebarTab::Notes, "Notes").clicked() {
-                            self.selected_tab = SidebarTab::Notes;
-                            self.selected_index = None;
-                            self.state_rename = false;
+                            self.run_action(Action::ShowNotes);
                         }
                         if ui.selectable_label(self.selected_tab == SidebarTab::Trash, "Trash").clicked() {
-                            self.selected_tab = SidebarTab::Trash;
-                            self.selected_index = None;
-                            self.state_rename = false;
+                            self.run_action(Action::ShowTrash);
                         }
                     });
 
@@ -36,37 +35,121 @@ impl App {
                 }
             });
         
+        if self.state_calendar_open {
+            self.show_calendar(ctx);
+        }
+
+        if self.state_outline_open && self.selected_tab == SidebarTab::Notes {
+            self.show_outline(ctx);
+        }
+
         egui::CentralPanel::default()
             .show(ctx, |ui| {
-                egui::ScrollArea::vertical()
-                    .show(ui, |ui| {
-                        let total_width = ui.available_width();
-                        let half_width = total_width / 2.0;
-
-                        ui.horizontal(|ui| {
-                            ui.vertical(|ui| {
-                                if self.state_is_right_panel_on {
-                                    ui.set_width(half_width);
-                                }
-                                if let Some(_) = self.selected_index {
-                                    ui.add(
-                                        egui::TextEdit::multiline(&mut self.edited_content)
-                                            .lock_focus(true)
-                                            .desired_width(f32::INFINITY)
-                                    );
-                                }
-                            });
-                       
-                            if self.state_is_right_panel_on {
-                                ui.separator();
+                if self.selected_tab == SidebarTab::Trash {
+                    self.show_trash_preview(ui, ctx);
+                    return;
+                }
+                // editor and preview scroll on their own, the preview follows the editor
+                if self.state_is_right_panel_on {
+                    ui.columns(2, |columns| {
+                        if let [editor, preview] = columns {
+                            self.show_editor(editor);
+                            self.show_preview(preview, ctx);
+                        }
+                    });
+                } else {
+                    self.show_editor(ui);
+                }
+            });
+    }
 
-                                ui.vertical(|ui| {
-                                    ui.set_width(half_width);
-                                    render_md(ui, ctx, &self.edited_content);
-                                });
-                            }
-                        });
+    fn show_editor(&mut self, ui: &mut egui::Ui) {
+        if self.locked_note_id.is_some() && self.locked_note_id == self.selected_index {
+            self.show_locked_note(ui);
+            return;
+        }
+        if self.selected_index.is_none() {
+            return;
+        }
+        let options = self.markdown_extensions.0;
+        let mut layouter = |ui: &egui::Ui, text: &str, wrap_width: f32| {
+            let mut job = highlight_md(ui.ctx(), ui.style(), text, options);
+            job.wrap.max_width = wrap_width;
+            ui.fonts(|f| f.layout_job(job))
+        };
+        let scroll = egui::ScrollArea::vertical()
+            .id_salt("editor scroll")
+            .auto_shrink([false; 2])
+            .show(ui, |ui| {
+                let mut output = egui::TextEdit::multiline(&mut self.edited_content)
+                    .lock_focus(true)
+                    .desired_width(f32::INFINITY)
+                    .min_size(ui.available_size())
+                    .layouter(&mut layouter)
+                    .show(ui);
+                if let Some(offset) = self.editor_jump.take() {
+                    self.jump_editor(ui, &mut output, offset);
+                }
+                output
             });
-        });
+        self.sync_preview(&scroll);
     }
+
+    fn show_preview(&mut self, ui: &mut egui::Ui, ctx: &egui::Context) {
+        let mut area = egui::ScrollArea::vertical()
+            .id_salt("preview scroll")
+            .auto_shrink([false; 2]);
+        if let Some(y) = self.preview_scroll_to.take() {
+            area = area.vertical_scroll_offset(y);
+        }
+        let opened_note = area.show(ui, |ui| {
+            let note = self.selected_index.and_then(|id| note_name(&self.names, id));
+            let (cache, names, db_path) = (&mut self.embed_cache, &self.names, &self.db_path);
+            let embeds = Embeds::new(note, |name| cache.load(db_path, names, name));
+            let response = render_md(ui, ctx, &self.edited_content, self.markdown_extensions.0, embeds);
+            // a clicked task edits the note like typing does
+            if let Some(offset) = response.toggled_task {
+                toggle_task(&mut self.edited_content, offset);
+            }
+            self.preview_map = response.source_map;
+            response.opened_note
+        }).inner;
+
+        if let Some(name) = opened_note {
+            match find_note(&self.names, &name) {
+                Some(id) => self.open_search_result(id, false),
+                None => self.status_error = format!("Note not found: {name}"),
+            }
+        }
+    }
+
+    /// Scrolls the preview to the part of the note at the editor cursor,
+    /// or at the top of the editor when it's scrolled without moving the cursor
+    fn sync_preview(&mut self, scroll: &egui::scroll_area::ScrollAreaOutput<egui::text_edit::TextEditOutput>) {
+        let output = &scroll.inner;
+        let scroll_y = scroll.state.offset.y;
+        let cursor = output.cursor_range.map(|x| x.primary.ccursor);
+        let cursor_offset = cursor.map(|x| byte_offset(&self.edited_content, x.index));
+
+        if let Some(ccursor) = cursor
+            && let Some(offset) = cursor_offset
+            && cursor_offset != self.editor_cursor {
+            // keep the block at the height of the cursor
+            let cursor_y = output.galley_pos.y + output.galley.pos_from_ccursor(ccursor).top()
+                - scroll.inner_rect.top();
+            self.preview_scroll_to = Some((self.preview_map.y(offset) - cursor_y).max(0.0));
+        } else if (scroll_y - self.editor_scroll).abs() > 0.5 {
+            let galley_top = output.galley_pos.y - scroll.inner_rect.top() + scroll_y;
+            let top = output.galley.cursor_from_pos(egui::vec2(0.0, scroll_y - galley_top));
+            let offset = byte_offset(&self.edited_content, top.ccursor.index);
+            self.preview_scroll_to = Some(self.preview_map.y(offset));
+        }
+        self.editor_cursor = cursor_offset;
+        self.editor_scroll = scroll_y;
+    }
+}
+
+/// Char index of the editor -> byte offset in the text
+fn byte_offset(text: &str, index: usize) -> usize {
+    text.char_indices().nth(index).map_or(text.len(), |(i, _)| i)
 }
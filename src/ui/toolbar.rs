@@ -1,4 +1,5 @@
 use eframe::egui::{self, Align, Layout, Color32};
+use crate::actions::Action;
 use crate::app::{App};
 use crate::ui::toggle_compact::toggle;
 
@@ -31,7 +32,7 @@ impl App {
                     egui::ImageButton::new(ICON_ADD)
                         .tint(tint)
                 ).clicked() {
-                    self.state_add_new_note = true;
+                    self.run_action(Action::NewNote);
                 }
                 let save = ui.add_sized(
                     [20.0, 20.0],
@@ -39,14 +40,14 @@ impl App {
                         .tint(Color32::LIGHT_RED)
                 );
                 if save.clicked() {
-                    let _ = self.try_update_note_content();
+                    self.run_action(Action::SaveNote);
                 }
                 if ui.add_sized(
                     [20.0, 20.0],
                     egui::ImageButton::new(ICON_REFRESH)
                         .tint(tint)
                 ).clicked() {
-                    self.load_rows = false;
+                    self.run_action(Action::ReloadNotes);
                 }
                 
                 ui.add_space(5.0);
@@ -55,10 +56,10 @@ impl App {
                 
                 ui.add_space(5.0);
 
-                let is_enabled = self.edited_note_id.is_some();
+                let is_enabled = self.is_action_enabled(Action::History);
                 if ui.add_enabled(is_enabled, egui::Button::new("History")).
                     clicked() {
-                    self.state_history_open = true;
+                    self.run_action(Action::History);
                 };
 
             });
@@ -1,7 +1,12 @@
 use std::error::Error;
 use log::{info, error};
 use eframe::egui;
-use crate::app::{App};
+use chrono::{NaiveDateTime, Utc};
+use crate::app::{App, TrashAction};
+use crate::db::models::{TrashItem};
+use crate::embeds::Embeds;
+use crate::markdown::render_md;
+use crate::constants::DEFAULT_TRASH_RETENTION_DAYS;
 
 impl App {
     pub fn show_trash(&mut self, ui: &mut egui::Ui) 
@@ -31,51 +36,215 @@ impl App {
                 .auto_shrink([false; 2]) // basically false, false
                 .show(ui, |ui| {
                 // for borrow issues
-                let xs: Vec<(i64, String)> = self.notes_deleted.iter()
-                    .map(|(id, name)| (*id, name.clone()))
-                    .collect();
-                for (id, name) in xs {
-                    let selected = Some(&id) == self.selected_index.as_ref();
-
-                    let response = ui.add(egui::SelectableLabel::new(selected, name));
-
-                    if response.clicked() {
-                        self.selected_index = Some(id);
-                        // let _ = self.try_get_note(note.id);
-                        println!("Trash note clicked {:?}", self.selected_index);
+                let xs = self.notes_deleted.clone();
+                for x in &xs {
+                    self.draw_trash_item(ui, x);
+                }
+            });
+        }
+        Ok(()) 
+    }
+
+    fn draw_trash_item(&mut self, ui: &mut egui::Ui, x: &TrashItem) {
+        let id = x.id;
+        let selected = Some(&id) == self.selected_index.as_ref();
+
+        let response = if x.children.is_empty() {
+            let response = ui.add(egui::SelectableLabel::new(selected, &x.name));
+            ui.label(egui::RichText::new(deleted_ago(&x.deleted_at)).small().weak());
+            response
+        } else {
+            // get selection color from the theme
+            let selection_color = ui.style().visuals.selection.bg_fill;
+
+            let mut text = egui::RichText::new(&x.name);
+            if selected {
+                text = text.background_color(selection_color);
+            }
+            let response = egui::CollapsingHeader::new(text)
+                .id_salt(("trash", id))
+                .default_open(false)
+                .show(ui, |ui| {
+                    for child in &x.children {
+                        self.draw_trash_item(ui, child);
                     }
+                });
+            ui.label(egui::RichText::new(deleted_ago(&x.deleted_at)).small().weak());
+            response.header_response
+        };
+
+        if response.clicked() {
+            self.selected_index = Some(id);
+            let _ = self.try_get_trash_note(id, x.parent_id);
+        }
 
-                    // right btn
-                    response.context_menu(|ui| {
-                        ui.set_min_width(120.0);
-                        if ui.button("Restore").clicked() {
-                            let _ = self.try_restore_note(id);
-                            ui.close_menu();
-                        }
-
-                        if ui.button("Permanently Delete").clicked() {
-                            let _ = self.try_permanently_delete(id);
-                            ui.close_menu();
-                        }
-                        
-                        if ui.button("Empty trash").clicked() {
-                            let _ = self.try_permanently_delete_all();
-                            ui.close_menu();
-                        }
-                    });
+        // right btn
+        response.context_menu(|ui| {
+            ui.set_min_width(120.0);
+            if ui.button("Restore").clicked() {
+                if x.parent_deleted {
+                    // parent is still in the trash
+                    self.trash_confirm = Some(TrashAction::RestoreToRoot(id, x.name.clone()));
+                } else {
+                    let _ = self.try_restore_note(id, false);
+                }
+                ui.close_menu();
+            }
+
+            if ui.button("Permanently Delete").clicked() {
+                self.trash_confirm = Some(TrashAction::Delete(id, x.name.clone()));
+                ui.close_menu();
+            }
+            
+            if ui.button("Empty trash").clicked() {
+                self.trash_confirm = Some(TrashAction::Empty);
+                ui.close_menu();
+            }
+        });
+    }
+
+    /// Read-only preview of the selected note in the trash
+    pub fn show_trash_preview(&mut self, ui: &mut egui::Ui, ctx: &egui::Context) {
+        let note = match &self.trash_preview {
+            Some(x) if Some(x.id) == self.selected_index => x,
+            _ => {
+                ui.label("Select a note to preview it");
+                return;
+            }
+        };
+
+        ui.heading(&note.name);
+        if let Some(deleted_at) = &note.deleted_at {
+            ui.label(egui::RichText::new(deleted_ago(deleted_at)).weak());
+        }
+        let parent = match &self.trash_preview_parent {
+            Some(x) => format!("Former parent: {}", x),
+            None => "Former parent: none (top level)".to_string(),
+        };
+        ui.label(egui::RichText::new(parent).weak());
+        ui.separator();
+
+        let content = note.content.clone().unwrap_or_default();
+        egui::ScrollArea::vertical()
+            .auto_shrink([false; 2])
+            .show(ui, |ui| {
+                let (cache, names, db_path) = (&mut self.embed_cache, &self.names, &self.db_path);
+                let embeds = Embeds::new(Some(&note.name), |name| cache.load(db_path, names, name));
+                if !crate::crypto::is_encrypted(&content) {
+                    render_md(ui, ctx, &content, self.markdown_extensions.0, embeds);
+                    return;
+                }
+                match self.note_keys.get(&note.id)
+                    .map(|key| crate::crypto::decrypt(key, &content)) {
+                    Some(Ok(x)) => { render_md(ui, ctx, &x, self.markdown_extensions.0, embeds); },
+                    _ => { ui.label("🔒 This note is encrypted"); }
                 }
             });
+    }
+
+    pub fn try_get_trash_note(&mut self, id: i64, parent_id: Option<i64>) -> Result<(), Box<dyn std::error::Error>> {
+        let db = crate::db::database::Database::new(&self.db_path)?;
+        self.trash_preview = Some(db.get_note(id)?);
+        self.trash_preview_parent = match parent_id {
+            Some(x) => db.get_note(x).ok().map(|x| x.name),
+            None => None,
+        };
+        Ok(())
+    }
+
+    pub fn show_trash_confirm(&mut self, ctx: &egui::Context) {
+        let (message, details, button) = match &self.trash_confirm {
+            Some(TrashAction::MoveToTrash(_, name)) => (
+                format!("Move \"{}\" and its children to the trash?", name),
+                "They can be restored from the trash.",
+                "Delete",
+            ),
+            Some(TrashAction::Delete(_, name)) => (
+                format!("Permanently delete \"{}\" and its children?", name),
+                "This cannot be undone.",
+                "Delete",
+            ),
+            Some(TrashAction::Empty) => (
+                format!("Permanently delete all {} notes in the trash?",
+                    count_trash_items(&self.notes_deleted)),
+                "This cannot be undone.",
+                "Delete",
+            ),
+            Some(TrashAction::RestoreToRoot(_, name)) => (
+                format!("Parent of \"{}\" is in the trash.", name),
+                "Restore it as a top level note?",
+                "Restore",
+            ),
+            None => return,
+        };
+
+        let mut open = true;
+        egui::Window::new("Confirm")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                ui.label(message);
+                ui.label(details);
+                ui.horizontal(|ui| {
+                    if ui.button(button).clicked() {
+                        let _ = match self.trash_confirm.take() {
+                            Some(TrashAction::MoveToTrash(id, _)) => {
+                                let res = self.try_delete_note(id);
+                                self.original_content = String::new();
+                                self.edited_content = String::new();
+                                res
+                            }
+                            Some(TrashAction::Delete(id, _)) => self.try_permanently_delete(id),
+                            Some(TrashAction::Empty) => self.try_permanently_delete_all(),
+                            Some(TrashAction::RestoreToRoot(id, _)) => self.try_restore_note(id, true),
+                            None => Ok(()),
+                        };
+                    }
+                    if ui.button("Cancel").clicked() {
+                        self.trash_confirm = None;
+                    }
+                });
+            });
+        if !open {
+            self.trash_confirm = None;
+        }
+    }
+
+    /// Permanently deletes notes older than the retention period, runs on open
+    pub fn purge_trash(&mut self) {
+        let days = self.config.trash_retention_days
+            .unwrap_or(DEFAULT_TRASH_RETENTION_DAYS);
+        if days == 0 {
+            return;
+        }
+
+        match crate::db::database::Database::new(&self.db_path)
+            .and_then(|mut db| db.purge_trash(days)) {
+            Ok(0) => {}
+            Ok(n) => {
+                info!("Purged {n} notes from the trash");
+                self.status_error = format!("{n} notes older than {days} days removed from the trash");
+                self.state_trash_load = false;
+            }
+            Err(e) => {
+                error!("Failed to purge trash: {e}");
+                self.status_error = format!("Error purging trash: {e}");
+            }
         }
-        Ok(()) 
     }
 
-    fn try_restore_note(&mut self, id: i64) -> Result<(), Box<dyn std::error::Error>> {
+    fn try_restore_note(&mut self, id: i64, to_root: bool) -> Result<(), Box<dyn std::error::Error>> {
         let mut db = crate::db::database::Database::new(&self.db_path)?;
-        self.status_error = crate::utils::result(db.restore_note(id), "Error restoring note");
+        self.status_error = crate::utils::result(
+            db.restore_note(id, to_root),
+            "Error restoring note");
 
         // refresh ui
         self.load_rows = false;
         self.state_trash_load = false;
+        self.trash_preview = None;
         Ok(())
     }
     
@@ -88,6 +257,7 @@ impl App {
         // refresh ui
         self.load_rows = false;
         self.state_trash_load = false;
+        self.trash_preview = None;
         Ok(())
     }
     
@@ -100,6 +270,27 @@ impl App {
         // refresh ui
         self.load_rows = false;
         self.state_trash_load = false;
+        self.trash_preview = None;
         Ok(())
     }
 }
+
+fn count_trash_items(xs: &[TrashItem]) -> usize {
+    xs.iter().map(|x| 1 + count_trash_items(&x.children)).sum()
+}
+
+/// "deleted 2025-01-01 (3 days ago)", deleted_at is UTC from CURRENT_TIMESTAMP
+fn deleted_ago(deleted_at: &str) -> String {
+    match NaiveDateTime::parse_from_str(deleted_at, "%Y-%m-%d %H:%M:%S") {
+        Ok(x) => {
+            let days = (Utc::now().naive_utc() - x).num_days();
+            let ago = match days {
+                0 => "today".to_string(),
+                1 => "1 day ago".to_string(),
+                _ => format!("{days} days ago"),
+            };
+            format!("deleted {} ({})", x.format("%Y-%m-%d"), ago)
+        }
+        Err(_) => format!("deleted {deleted_at}"),
+    }
+}